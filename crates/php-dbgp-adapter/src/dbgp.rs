@@ -0,0 +1,207 @@
+//! The DBGp protocol spoken by Xdebug. The engine sends XML packets preceded
+//! by their length and followed by a NUL byte; the IDE sends commands of the
+//! form `name -i transaction_id [options] [-- base64 data]`, each followed by
+//! a NUL byte.
+
+use std::io::{self, BufRead, Read, Write};
+use std::net::TcpStream;
+
+use crate::{base64, xml};
+
+/// Reads the next packet, or `None` once the engine has disconnected.
+pub fn read_packet(reader: &mut impl BufRead) -> io::Result<Option<xml::Element>> {
+    let mut length = Vec::new();
+    if reader.read_until(0, &mut length)? == 0 {
+        return Ok(None);
+    }
+    length.pop();
+    let length = std::str::from_utf8(&length)
+        .ok()
+        .and_then(|length| length.trim().parse::<usize>().ok())
+        .ok_or_else(|| invalid_data("invalid packet length"))?;
+
+    // The packet plus its terminating NUL byte.
+    let mut packet = vec![0; length + 1];
+    reader.read_exact(&mut packet)?;
+    packet.pop();
+    let packet = String::from_utf8_lossy(&packet);
+    xml::parse(&packet).map(Some).map_err(invalid_data)
+}
+
+fn invalid_data(error: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.into())
+}
+
+/// The sending half of a connection to a DBGp engine.
+pub struct Connection {
+    stream: TcpStream,
+    next_transaction_id: u32,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            next_transaction_id: 1,
+        }
+    }
+
+    /// Sends a command and returns its transaction ID, which the engine's
+    /// response carries.
+    pub fn send(&mut self, command: &str, options: &[(&str, &str)], data: Option<&str>) -> u32 {
+        let transaction_id = self.next_transaction_id;
+        self.next_transaction_id += 1;
+
+        let mut line = format!("{command} -i {transaction_id}");
+        for (option, value) in options {
+            line.push_str(&format!(" -{option} {}", quote(value)));
+        }
+        if let Some(data) = data {
+            line.push_str(" -- ");
+            line.push_str(&base64::encode(data.as_bytes()));
+        }
+        line.push('\0');
+
+        // A closed connection is noticed by the reading side.
+        let _ = self.stream.write_all(line.as_bytes());
+        transaction_id
+    }
+
+    pub fn close(&self) {
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+
+    pub fn try_clone_reader(&self) -> io::Result<impl Read + use<>> {
+        self.stream.try_clone()
+    }
+}
+
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains([' ', '"', '\\', '\0']) {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Converts a local path into the `file://` URI DBGp identifies files by.
+pub fn path_to_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        // Windows paths such as `C:/project`.
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+/// Converts a `file://` URI back into a path.
+pub fn uri_to_path(uri: &str) -> String {
+    let Some(path) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%'
+            && let Some(hex) = tail.get(..2)
+            && let Some(decoded) = std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            bytes.push(decoded);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    let path = String::from_utf8_lossy(&bytes).into_owned();
+
+    // `/C:/project` is a Windows path.
+    match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    fn packet(xml: &str) -> Vec<u8> {
+        format!("{}\0{xml}\0", xml.len()).into_bytes()
+    }
+
+    #[test]
+    fn reads_length_prefixed_packets() {
+        let mut data = packet(r#"<init fileuri="file:///app/index.php"/>"#);
+        data.extend(packet(r#"<response command="run" status="break"/>"#));
+        let mut reader = BufReader::new(Cursor::new(data));
+
+        let init = read_packet(&mut reader).unwrap().unwrap();
+        assert_eq!(init.name, "init");
+        assert_eq!(init.attribute("fileuri"), Some("file:///app/index.php"));
+        let response = read_packet(&mut reader).unwrap().unwrap();
+        assert_eq!(response.attribute("status"), Some("break"));
+        assert!(read_packet(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_lengths() {
+        let mut reader = BufReader::new(Cursor::new(b"abc\0<init/>\0".to_vec()));
+        assert!(read_packet(&mut reader).is_err());
+    }
+
+    #[test]
+    fn sends_commands_with_quoted_options_and_base64_data() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        let mut connection = Connection::new(client);
+        assert_eq!(connection.send("run", &[], None), 1);
+        let options = [("f", "file:///my app/a.php"), ("n", "3"), ("x", "")];
+        assert_eq!(
+            connection.send("breakpoint_set", &options, Some("$a > 1")),
+            2
+        );
+        connection.close();
+
+        let mut received = String::new();
+        BufReader::new(server)
+            .read_to_string(&mut received)
+            .unwrap();
+        assert_eq!(
+            received,
+            "run -i 1\0breakpoint_set -i 2 -f \"file:///my app/a.php\" -n 3 -x \"\" -- JGEgPiAx\0"
+        );
+    }
+
+    #[test]
+    fn converts_between_paths_and_uris() {
+        for (path, uri) in [
+            ("/app/index.php", "file:///app/index.php"),
+            ("/my app/ä.php", "file:///my%20app/%C3%A4.php"),
+            ("C:/project/index.php", "file:///C:/project/index.php"),
+        ] {
+            assert_eq!(path_to_uri(path), uri);
+            assert_eq!(uri_to_path(uri), path);
+        }
+        assert_eq!(
+            path_to_uri("C:\\project\\index.php"),
+            "file:///C:/project/index.php"
+        );
+        assert_eq!(uri_to_path("dbgp://1"), "dbgp://1");
+    }
+}
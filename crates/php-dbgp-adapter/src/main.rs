@@ -0,0 +1,101 @@
+//! A debug adapter for PHP that translates between the Debug Adapter Protocol
+//! and the DBGp protocol spoken by Xdebug, without depending on Node.js. With
+//! `--phpdbg` it drives phpdbg instead.
+//!
+//! With `--server=<port>` the adapter accepts the editor's connection on that
+//! port; otherwise it talks DAP over stdin and stdout. `--version` prints the
+//! version and exits.
+
+mod base64;
+mod dap;
+mod dbgp;
+mod mappings;
+mod phpdbg;
+mod session;
+mod xml;
+
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process::ExitCode;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use dap::Client;
+
+fn main() -> ExitCode {
+    if env::args().any(|arg| arg == "--version") {
+        println!("php-dbgp-adapter {}", env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+
+    let port = env::args().find_map(|arg| arg.strip_prefix("--server=")?.parse::<u16>().ok());
+    let (reader, writer) = match connect(port) {
+        Ok(connection) => connection,
+        Err(error) => {
+            eprintln!("php-dbgp-adapter: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let client = Client::new(writer);
+    if env::args().any(|arg| arg == "--phpdbg") {
+        let (events, receiver) = read_requests(reader, phpdbg::Event::Request, || {
+            phpdbg::Event::ClientClosed
+        });
+        let mut session = phpdbg::Session::new(client, events);
+        run(receiver, |event| session.handle(event));
+    } else {
+        let (events, receiver) = read_requests(reader, session::Event::Request, || {
+            session::Event::ClientClosed
+        });
+        let mut session = session::Session::new(client, events);
+        run(receiver, |event| session.handle(event));
+    }
+    ExitCode::SUCCESS
+}
+
+type Connection = (Box<dyn BufRead + Send>, Box<dyn Write + Send>);
+
+fn connect(port: Option<u16>) -> io::Result<Connection> {
+    let Some(port) = port else {
+        return Ok((
+            Box::new(BufReader::new(io::stdin())),
+            Box::new(io::stdout()),
+        ));
+    };
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let (stream, _) = listener.accept()?;
+    Ok((
+        Box::new(BufReader::new(stream.try_clone()?)),
+        Box::new(stream),
+    ))
+}
+
+/// Reads the editor's requests on a separate thread, turning them into
+/// events of the session.
+fn read_requests<E: Send + 'static>(
+    mut reader: Box<dyn BufRead + Send>,
+    request: fn(serde_json::Value) -> E,
+    closed: fn() -> E,
+) -> (mpsc::Sender<E>, Receiver<E>) {
+    let (events, receiver) = mpsc::channel();
+    let requests = events.clone();
+    thread::spawn(move || {
+        while let Ok(Some(message)) = dap::read_message(&mut reader) {
+            if requests.send(request(message)).is_err() {
+                return;
+            }
+        }
+        let _ = requests.send(closed());
+    });
+    (events, receiver)
+}
+
+fn run<E>(receiver: Receiver<E>, mut handle: impl FnMut(E) -> bool) {
+    for event in receiver {
+        if !handle(event) {
+            break;
+        }
+    }
+}
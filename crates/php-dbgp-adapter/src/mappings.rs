@@ -0,0 +1,102 @@
+//! Translation between the paths the engine sees, e.g. inside a container,
+//! and the paths of the same files in the editor.
+
+use serde_json::Value;
+
+pub struct PathMappings {
+    /// Pairs of server and local directories.
+    mappings: Vec<(String, String)>,
+}
+
+impl PathMappings {
+    /// Reads a `pathMappings` object of server paths to local paths.
+    pub fn new(mappings: Option<&Value>) -> Self {
+        let mappings = mappings
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(server, local)| Some((normalize(server), normalize(local.as_str()?))))
+            .collect();
+        Self { mappings }
+    }
+
+    pub fn to_server(&self, local_path: &str) -> String {
+        self.translate(local_path, |(server, local)| (local, server))
+    }
+
+    pub fn to_local(&self, server_path: &str) -> String {
+        self.translate(server_path, |(server, local)| (server, local))
+    }
+
+    /// Replaces the longest matching `from` directory of the mappings with the
+    /// corresponding `to` directory.
+    fn translate<'a>(
+        &'a self,
+        path: &str,
+        direction: impl Fn(&'a (String, String)) -> (&'a String, &'a String),
+    ) -> String {
+        let path = normalize(path);
+        self.mappings
+            .iter()
+            .map(direction)
+            .filter_map(|(from, to)| {
+                let rest = path.strip_prefix(from.as_str())?;
+                (rest.is_empty() || rest.starts_with('/') || from.ends_with('/'))
+                    .then(|| (from.len(), format!("{to}{rest}")))
+            })
+            .max_by_key(|(length, _)| *length)
+            .map(|(_, translated)| translated)
+            .unwrap_or(path)
+    }
+}
+
+fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    match path.trim_end_matches('/') {
+        "" => path,
+        trimmed => trimmed.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn translates_with_the_longest_matching_directory() {
+        let mappings = json!({
+            "/var/www": "/home/me/project",
+            "/var/www/vendor": "/home/me/vendor",
+            "C:\\server\\": "/home/me/windows",
+        });
+        let mappings = PathMappings::new(Some(&mappings));
+
+        for (server, local) in [
+            ("/var/www/index.php", "/home/me/project/index.php"),
+            (
+                "/var/www/vendor/autoload.php",
+                "/home/me/vendor/autoload.php",
+            ),
+            ("/var/www", "/home/me/project"),
+            ("C:/server/app.php", "/home/me/windows/app.php"),
+            ("/elsewhere/index.php", "/elsewhere/index.php"),
+        ] {
+            assert_eq!(mappings.to_local(server), local);
+            assert_eq!(mappings.to_server(local), server);
+        }
+        // Only whole directories match.
+        assert_eq!(
+            mappings.to_local("/var/wwwroot/index.php"),
+            "/var/wwwroot/index.php"
+        );
+    }
+
+    #[test]
+    fn leaves_paths_alone_without_mappings() {
+        let mappings = PathMappings::new(None);
+        assert_eq!(mappings.to_local("/app/index.php"), "/app/index.php");
+        assert_eq!(mappings.to_server("C:\\app\\index.php"), "C:/app/index.php");
+    }
+}
@@ -0,0 +1,686 @@
+//! Debugging with phpdbg, PHP's built-in debugger, for environments that
+//! don't have Xdebug.
+//!
+//! phpdbg is driven through its command prompt on stdin and stdout. Every
+//! batch of commands ends with `ev` of a unique marker, so seeing the marker
+//! in the output means phpdbg has run the whole batch and waits for input
+//! again. Program state is read by evaluating PHP snippets that print JSON,
+//! rather than by parsing phpdbg's own listings.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use crate::dap::Client;
+
+/// There is only ever one PHP thread.
+const THREAD_ID: u64 = 1;
+
+const SYNC_MARKER: &str = "@@dap-sync:";
+const JSON_MARKER: &str = "@@dap-json@@";
+
+/// A PHP closure that describes a value as `[display value, type, whether
+/// it has children]`.
+const DESCRIBE: &str = "function ($c) { return is_array($c) ? ['array(' . count($c) . ')', 'array', count($c) > 0] : (is_object($c) ? [get_class($c), get_class($c), true] : (is_resource($c) ? ['resource', 'resource', false] : [var_export($c, true), gettype($c), false])); }";
+
+pub enum Event {
+    Request(Value),
+    ClientClosed,
+    Line(String),
+    ProcessExited(Option<i32>),
+}
+
+struct LineBreakpoint {
+    line: u64,
+    condition: Option<String>,
+}
+
+/// What to do once phpdbg has run a batch of commands.
+enum Operation {
+    Ignore,
+    Resume {
+        reason: &'static str,
+    },
+    StackTrace(Value),
+    Variables {
+        request: Value,
+        frame: u64,
+        root: String,
+        path: Vec<Value>,
+    },
+    Evaluate {
+        request: Value,
+        frame: u64,
+        expression: String,
+    },
+}
+
+/// A value whose children a DAP `variablesReference` lists: the result of
+/// `root`, evaluated in `frame`, followed down the array keys or raw object
+/// property names in `path`.
+struct Reference {
+    frame: u64,
+    root: String,
+    path: Vec<Value>,
+}
+
+pub struct Session {
+    client: Client,
+    events: Sender<Event>,
+    process: Option<Arc<Mutex<Child>>>,
+    stdin: Option<ChildStdin>,
+    program: String,
+    stop_on_entry: bool,
+    line_breakpoints: HashMap<String, Vec<LineBreakpoint>>,
+    breakpoints_changed: bool,
+    /// Batches sent to phpdbg that haven't finished yet, by marker.
+    batches: VecDeque<(u64, Operation)>,
+    next_marker: u64,
+    /// The output of the batch at the front of `batches` so far.
+    output: Vec<String>,
+    stopped: bool,
+    references: Vec<Reference>,
+}
+
+impl Session {
+    pub fn new(client: Client, events: Sender<Event>) -> Self {
+        Self {
+            client,
+            events,
+            process: None,
+            stdin: None,
+            program: String::new(),
+            stop_on_entry: false,
+            line_breakpoints: HashMap::new(),
+            breakpoints_changed: false,
+            batches: VecDeque::new(),
+            next_marker: 1,
+            output: Vec::new(),
+            stopped: false,
+            references: Vec::new(),
+        }
+    }
+
+    /// Handles an event, returning whether the session goes on.
+    pub fn handle(&mut self, event: Event) -> bool {
+        match event {
+            Event::Request(request) => return self.handle_request(request),
+            Event::ClientClosed => {
+                self.shut_down();
+                return false;
+            }
+            Event::Line(line) => self.handle_line(line),
+            Event::ProcessExited(code) => {
+                self.process = None;
+                self.stdin = None;
+                self.client
+                    .event("exited", json!({ "exitCode": code.unwrap_or(1) }));
+                self.client.event("terminated", json!({}));
+            }
+        }
+        true
+    }
+
+    fn handle_request(&mut self, request: Value) -> bool {
+        let arguments = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsEvaluateForHovers": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(arguments).map(|()| json!({})),
+            "attach" => Err("phpdbg can only launch scripts".to_string()),
+            "configurationDone" => {
+                let mut commands = self.breakpoint_commands();
+                if self.stop_on_entry {
+                    // phpdbg moves breakpoints to the next line with code.
+                    commands.push(format!("break {}:1", self.program));
+                }
+                commands.push("run".to_string());
+                let reason = if self.stop_on_entry {
+                    "entry"
+                } else {
+                    "breakpoint"
+                };
+                self.send(commands, Operation::Resume { reason });
+                Ok(json!({}))
+            }
+            "setBreakpoints" => Ok(self.set_breakpoints(arguments)),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "phpdbg" }] })),
+            "stackTrace" => {
+                return self.send_for(&request, vec!["back".to_string()], |request| {
+                    Operation::StackTrace(request)
+                });
+            }
+            "scopes" => {
+                let frame = arguments["frameId"].as_u64().unwrap_or_default();
+                let reference = self.reference(frame, "get_defined_vars()".to_string(), Vec::new());
+                Ok(json!({
+                    "scopes": [{ "name": "Locals", "variablesReference": reference, "expensive": false }],
+                }))
+            }
+            "variables" => return self.variables(&request),
+            "evaluate" => {
+                let frame = arguments["frameId"].as_u64().unwrap_or_default();
+                // Commands are single lines.
+                let expression = arguments["expression"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .replace(['\r', '\n'], " ");
+                let snippet = format!(
+                    "(function ($c) {{ $d = {DESCRIBE}; return '{JSON_MARKER}' . json_encode($d($c), JSON_PARTIAL_OUTPUT_ON_ERROR | JSON_INVALID_UTF8_SUBSTITUTE); }})(({expression}))"
+                );
+                let commands = in_frame(frame, format!("ev {snippet}"));
+                return self.send_for(&request, commands, |request| Operation::Evaluate {
+                    request,
+                    frame,
+                    expression,
+                });
+            }
+            "continue" => self.resume("continue", "breakpoint"),
+            "next" => self.resume("next", "step"),
+            "stepIn" => self.resume("step", "step"),
+            "stepOut" => self.resume("leave", "step"),
+            "terminate" => {
+                self.shut_down();
+                self.client.respond(&request, json!({}));
+                return true;
+            }
+            "disconnect" => {
+                self.shut_down();
+                self.client.respond(&request, json!({}));
+                return false;
+            }
+            command => Err(format!("Unsupported request: {command}")),
+        };
+
+        match result {
+            Ok(body) => {
+                self.client.respond(&request, body);
+                if command == "launch" {
+                    self.client.event("initialized", json!({}));
+                }
+            }
+            Err(message) => self.client.fail(&request, &message),
+        }
+        true
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<(), String> {
+        let strings = |value: &Value| {
+            value
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>()
+        };
+
+        self.program = arguments["program"]
+            .as_str()
+            .ok_or("phpdbg needs a `program` to run")?
+            .to_string();
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or_default();
+
+        let phpdbg = arguments["runtimeExecutable"].as_str().unwrap_or("phpdbg");
+        let mut command = Command::new(phpdbg);
+        // Quiet and without colours, so the output is easy to read back.
+        command
+            .args(strings(&arguments["runtimeArgs"]))
+            .args(["-q", "-b"])
+            .arg(&self.program)
+            .args(strings(&arguments["args"]))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(cwd) = arguments["cwd"].as_str() {
+            command.current_dir(cwd);
+        }
+        for (key, value) in arguments["env"].as_object().into_iter().flatten() {
+            if let Some(value) = value.as_str() {
+                command.env(key, value);
+            }
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|error| format!("Could not run {phpdbg}: {error}"))?;
+        self.stdin = child.stdin.take();
+        for output in [
+            child
+                .stdout
+                .take()
+                .map(|stdout| Box::new(stdout) as Box<dyn std::io::Read + Send>),
+            child
+                .stderr
+                .take()
+                .map(|stderr| Box::new(stderr) as Box<dyn std::io::Read + Send>),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            let events = self.events.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(output);
+                let mut line = Vec::new();
+                while reader
+                    .read_until(b'\n', &mut line)
+                    .is_ok_and(|length| length > 0)
+                {
+                    let text = String::from_utf8_lossy(&line).trim_end().to_string();
+                    if events.send(Event::Line(text)).is_err() {
+                        break;
+                    }
+                    line.clear();
+                }
+            });
+        }
+
+        let child = Arc::new(Mutex::new(child));
+        self.process = Some(child.clone());
+        let events = self.events.clone();
+        thread::spawn(move || {
+            loop {
+                let status = child.lock().map(|mut child| child.try_wait());
+                match status {
+                    Ok(Ok(None)) => thread::sleep(Duration::from_millis(50)),
+                    Ok(Ok(Some(status))) => {
+                        let _ = events.send(Event::ProcessExited(status.code()));
+                        break;
+                    }
+                    _ => {
+                        let _ = events.send(Event::ProcessExited(None));
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn shut_down(&mut self) {
+        if let Some(stdin) = &mut self.stdin {
+            let _ = stdin.write_all(b"quit\n");
+        }
+        self.stdin = None;
+        if let Some(process) = self.process.take()
+            && let Ok(mut child) = process.lock()
+        {
+            let _ = child.kill();
+        }
+    }
+
+    fn handle_line(&mut self, line: String) {
+        // phpdbg prints its prompt without a newline, so it starts the
+        // next line of output.
+        let mut line = line.as_str();
+        while let Some(rest) = line.strip_prefix("prompt>") {
+            line = rest.trim_start();
+        }
+
+        if let Some(marker) = line
+            .find(SYNC_MARKER)
+            .and_then(|start| line[start + SYNC_MARKER.len()..].split('@').next())
+            .and_then(|marker| marker.parse::<u64>().ok())
+        {
+            if self
+                .batches
+                .front()
+                .is_some_and(|(front, _)| *front == marker)
+                && let Some((_, operation)) = self.batches.pop_front()
+            {
+                let output = std::mem::take(&mut self.output);
+                self.finish(operation, output);
+            }
+            return;
+        }
+
+        let is_running = matches!(self.batches.front(), Some((_, Operation::Resume { .. })));
+        if is_running && !is_phpdbg_message(line) {
+            self.client.output("stdout", format!("{line}\n"));
+        }
+        self.output.push(line.to_string());
+    }
+
+    fn finish(&mut self, operation: Operation, output: Vec<String>) {
+        match operation {
+            Operation::Ignore => {}
+            Operation::Resume { reason } => {
+                if output.iter().any(|line| line.contains("[Script ended")) {
+                    // phpdbg would wait for a new `run`; exiting ends the session.
+                    if let Some(stdin) = &mut self.stdin {
+                        let _ = stdin.write_all(b"quit\n");
+                    }
+                    return;
+                }
+
+                self.stopped = true;
+                self.references.clear();
+                if self.breakpoints_changed {
+                    let commands = self.breakpoint_commands();
+                    self.send(commands, Operation::Ignore);
+                }
+
+                let mut body = json!({
+                    "reason": reason,
+                    "threadId": THREAD_ID,
+                    "allThreadsStopped": true,
+                });
+                if output.iter().any(|line| line.contains("Breakpoint #")) {
+                    body["reason"] = "breakpoint".into();
+                }
+                if let Some(exception) = output.iter().find(|line| line.contains("[Uncaught ")) {
+                    body["reason"] = "exception".into();
+                    body["text"] = exception.trim_matches(['[', ']']).into();
+                }
+                self.client.event("stopped", body);
+            }
+            Operation::StackTrace(request) => {
+                let frames = output
+                    .iter()
+                    .filter_map(|line| stack_frame(line))
+                    .collect::<Vec<_>>();
+                let total = frames.len();
+                self.client.respond(
+                    &request,
+                    json!({ "stackFrames": frames, "totalFrames": total }),
+                );
+            }
+            Operation::Variables {
+                request,
+                frame,
+                root,
+                path,
+            } => {
+                let is_scope = path.is_empty() && root == "get_defined_vars()";
+                match json_result(&output) {
+                    Ok(Value::Array(children)) => {
+                        let variables = children
+                            .into_iter()
+                            .filter_map(|child| {
+                                let [key, name, description] =
+                                    <[Value; 3]>::try_from(child.as_array()?.clone()).ok()?;
+                                let name = name.as_str()?.to_string();
+                                let name = if is_scope { format!("${name}") } else { name };
+                                let mut path = path.clone();
+                                path.push(key);
+                                Some(self.variable(name, &description, frame, root.clone(), path))
+                            })
+                            .collect::<Vec<_>>();
+                        self.client
+                            .respond(&request, json!({ "variables": variables }));
+                    }
+                    Ok(_) => self.client.respond(&request, json!({ "variables": [] })),
+                    Err(error) => self.client.fail(&request, &error),
+                }
+            }
+            Operation::Evaluate {
+                request,
+                frame,
+                expression,
+            } => match json_result(&output) {
+                Ok(description) => {
+                    let variable = self.variable(
+                        String::new(),
+                        &description,
+                        frame,
+                        format!("({expression})"),
+                        Vec::new(),
+                    );
+                    self.client.respond(
+                        &request,
+                        json!({
+                            "result": variable["value"],
+                            "type": variable["type"],
+                            "variablesReference": variable["variablesReference"],
+                        }),
+                    );
+                }
+                Err(error) => self.client.fail(&request, &error),
+            },
+        }
+    }
+
+    /// Turns a `[display value, type, has children]` description into a DAP
+    /// variable.
+    fn variable(
+        &mut self,
+        name: String,
+        description: &Value,
+        frame: u64,
+        root: String,
+        path: Vec<Value>,
+    ) -> Value {
+        let has_children = description[2].as_bool().unwrap_or_default();
+        let reference = if has_children {
+            self.reference(frame, root, path)
+        } else {
+            0
+        };
+        json!({
+            "name": name,
+            "value": description[0].as_str().unwrap_or_default(),
+            "type": description[1].as_str().unwrap_or_default(),
+            "variablesReference": reference,
+        })
+    }
+
+    fn variables(&mut self, request: &Value) -> bool {
+        let Some(reference) = request["arguments"]["variablesReference"]
+            .as_u64()
+            .and_then(|reference| self.references.get((reference as usize).checked_sub(1)?))
+        else {
+            self.client.fail(request, "Unknown variables reference");
+            return true;
+        };
+
+        // The path is JSON inside a single-quoted PHP string.
+        let path_json = Value::from(reference.path.clone())
+            .to_string()
+            .replace('\\', "\\\\")
+            .replace('\'', "\\'");
+        let snippet = format!(
+            "(function ($v, $p) {{ foreach ($p as $k) {{ $v = is_object($v) ? ((array) $v)[$k] : $v[$k]; }} $d = {DESCRIBE}; $r = []; foreach ((is_object($v) ? (array) $v : $v) as $k => $c) {{ $n = is_string($k) && strpos($k, \"\\0\") !== false ? substr($k, strrpos($k, \"\\0\") + 1) : $k; $r[] = [$k, (string) $n, $d($c)]; }} return '{JSON_MARKER}' . json_encode($r, JSON_PARTIAL_OUTPUT_ON_ERROR | JSON_INVALID_UTF8_SUBSTITUTE); }})({}, json_decode('{path_json}', true))",
+            reference.root
+        );
+        let (frame, root, path) = (
+            reference.frame,
+            reference.root.clone(),
+            reference.path.clone(),
+        );
+        let commands = in_frame(frame, format!("ev {snippet}"));
+        self.send_for(request, commands, |request| Operation::Variables {
+            request,
+            frame,
+            root,
+            path,
+        })
+    }
+
+    fn resume(&mut self, command: &str, reason: &'static str) -> Result<Value, String> {
+        if !self.stopped {
+            return Err("The script is running".into());
+        }
+        self.stopped = false;
+        self.references.clear();
+        self.send(vec![command.to_string()], Operation::Resume { reason });
+        Ok(json!({ "allThreadsContinued": true }))
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Value {
+        let path = arguments["source"]["path"].as_str().unwrap_or_default();
+        let breakpoints = arguments["breakpoints"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|breakpoint| {
+                Some(LineBreakpoint {
+                    line: breakpoint["line"].as_u64()?,
+                    condition: breakpoint["condition"].as_str().map(ToOwned::to_owned),
+                })
+            })
+            .collect::<Vec<_>>();
+        let body = breakpoints
+            .iter()
+            .map(|breakpoint| json!({ "verified": true, "line": breakpoint.line }))
+            .collect::<Vec<_>>();
+        self.line_breakpoints.insert(path.to_string(), breakpoints);
+
+        // phpdbg only takes commands while the script is paused.
+        self.breakpoints_changed = true;
+        if self.stopped {
+            let commands = self.breakpoint_commands();
+            self.send(commands, Operation::Ignore);
+        }
+        json!({ "breakpoints": body })
+    }
+
+    /// Commands that replace phpdbg's breakpoints with the current ones.
+    fn breakpoint_commands(&mut self) -> Vec<String> {
+        self.breakpoints_changed = false;
+        let mut commands = vec!["clear".to_string()];
+        for (path, breakpoints) in &self.line_breakpoints {
+            for breakpoint in breakpoints {
+                commands.push(match &breakpoint.condition {
+                    Some(condition) => format!(
+                        "break at {path}:{} if {}",
+                        breakpoint.line,
+                        condition.replace(['\r', '\n'], " ")
+                    ),
+                    None => format!("break {path}:{}", breakpoint.line),
+                });
+            }
+        }
+        commands
+    }
+
+    fn reference(&mut self, frame: u64, root: String, path: Vec<Value>) -> usize {
+        self.references.push(Reference { frame, root, path });
+        self.references.len()
+    }
+
+    /// Sends commands for a DAP request, failing the request if phpdbg can't
+    /// take commands. Returns whether the session goes on.
+    fn send_for(
+        &mut self,
+        request: &Value,
+        commands: Vec<String>,
+        operation: impl FnOnce(Value) -> Operation,
+    ) -> bool {
+        if self.stopped {
+            self.send(commands, operation(request.clone()));
+        } else {
+            self.client.fail(request, "The script is running");
+        }
+        true
+    }
+
+    fn send(&mut self, commands: Vec<String>, operation: Operation) {
+        let Some(stdin) = &mut self.stdin else {
+            return;
+        };
+        let marker = self.next_marker;
+        self.next_marker += 1;
+
+        let mut input = String::new();
+        for command in commands {
+            input.push_str(&command);
+            input.push('\n');
+        }
+        input.push_str(&format!("ev '{SYNC_MARKER}{marker}@@'\n"));
+        if stdin.write_all(input.as_bytes()).is_ok() {
+            self.batches.push_back((marker, operation));
+        }
+    }
+}
+
+/// Wraps a command so that it runs in the given stack frame.
+fn in_frame(frame: u64, command: String) -> Vec<String> {
+    if frame == 0 {
+        vec![command]
+    } else {
+        vec![format!("frame {frame}"), command, "frame 0".to_string()]
+    }
+}
+
+/// Returns whether a line of output comes from phpdbg rather than from the
+/// script: `[messages]`, source listings and stack frames.
+fn is_phpdbg_message(line: &str) -> bool {
+    let is_listing = line.starts_with(['>', ' '])
+        && line
+            .get(1..6)
+            .is_some_and(|number| number.bytes().all(|byte| byte.is_ascii_digit()))
+        && line.get(6..).is_some_and(|rest| rest.starts_with(':'));
+    (line.starts_with('[') && line.ends_with(']')) || is_listing || line.starts_with("frame #")
+}
+
+/// Parses a line of `back` output such as `frame #1: foo() at /app/a.php:9`.
+fn stack_frame(line: &str) -> Option<Value> {
+    let rest = line.strip_prefix("frame #")?;
+    let (id, rest) = rest.split_once(": ")?;
+    let id = id.parse::<u64>().ok()?;
+    let (name, location) = rest.rsplit_once(" at ")?;
+    let (path, line) = location.rsplit_once(':')?;
+    let name_in_path = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    Some(json!({
+        "id": id,
+        "name": name,
+        "source": { "name": name_in_path, "path": path },
+        "line": line.parse::<u64>().ok()?,
+        "column": 1,
+    }))
+}
+
+/// Returns the JSON a snippet printed, or phpdbg's output as the error.
+fn json_result(output: &[String]) -> Result<Value, String> {
+    output
+        .iter()
+        .find_map(|line| {
+            let start = line.find(JSON_MARKER)?;
+            serde_json::from_str(&line[start + JSON_MARKER.len()..]).ok()
+        })
+        .ok_or_else(|| {
+            let message = output
+                .iter()
+                .map(|line| line.trim_matches(['[', ']']))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if message.is_empty() {
+                "phpdbg could not evaluate the expression".to_string()
+            } else {
+                message
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_phpdbg_messages() {
+        for (line, expected) in [
+            ("[Breakpoint #0 added at /app/index.php:3]", true),
+            (">00003: echo 'hi';", true),
+            (" 00004: }", true),
+            ("frame #0: {main} at /app/index.php:3", true),
+            ("Hello, world", false),
+            (" 0003", false),
+            ("     über", false),
+            ("[not closed", false),
+        ] {
+            assert_eq!(is_phpdbg_message(line), expected, "{line:?}");
+        }
+    }
+}
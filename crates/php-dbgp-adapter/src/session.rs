@@ -0,0 +1,1591 @@
+//! A debugging session: translates DAP requests from the editor into DBGp
+//! commands for the engine, and the engine's responses back into DAP
+//! responses and events.
+//!
+//! Everything that happens is an [`Event`] on a single channel, fed by one
+//! thread per connection and per output stream of the launched script.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use crate::base64;
+use crate::dap::Client;
+use crate::dbgp::{self, Connection};
+use crate::mappings::PathMappings;
+use crate::xml::Element;
+
+const DEFAULT_PORT: u16 = 9003;
+
+/// Exception breakpoint filters, named after the PHP error or exception class
+/// Xdebug matches them against.
+const EXCEPTION_FILTERS: &[(&str, &str)] = &[
+    ("Notice", "Notices"),
+    ("Warning", "Warnings"),
+    ("Error", "Errors"),
+    ("Exception", "Exceptions"),
+    ("*", "Everything"),
+];
+
+/// How many children of an array or object the engine sends at once.
+const MAX_CHILDREN: &str = "100";
+
+pub enum Event {
+    Request(Value),
+    ClientClosed,
+    EngineConnected(TcpStream),
+    Packet(u32, Element),
+    EngineClosed(u32),
+    Output(&'static str, String),
+    ProcessExited(Option<i32>),
+}
+
+struct LineBreakpoint {
+    line: u64,
+    condition: Option<String>,
+    hit_condition: Option<String>,
+}
+
+/// A connection from the engine, i.e. one PHP request or script run.
+struct Engine {
+    id: u32,
+    connection: Connection,
+    /// Whether the engine is executing code and can't take commands.
+    running: bool,
+    breakpoint_ids: Vec<String>,
+    /// The `Session::breakpoints_generation` the engine's breakpoints match.
+    breakpoints_generation: u64,
+}
+
+/// What to do with the engine's response to a command.
+enum Pending {
+    Ignore,
+    BreakpointSet {
+        generation: u64,
+    },
+    Resume {
+        reason: &'static str,
+    },
+    StackTrace(Value),
+    Scopes {
+        request: Value,
+        depth: u64,
+    },
+    Variables {
+        request: Value,
+        depth: u64,
+        context: u64,
+        property: bool,
+    },
+    Evaluate {
+        request: Value,
+        depth: u64,
+    },
+}
+
+/// What a DAP `variablesReference` refers to.
+enum Reference {
+    Context {
+        depth: u64,
+        context: u64,
+    },
+    Property {
+        depth: u64,
+        context: u64,
+        fullname: String,
+    },
+    /// Children that can't be fetched again, such as those of an `eval` result.
+    Values(Vec<Value>),
+}
+
+pub struct Session {
+    client: Client,
+    events: Sender<Event>,
+    mappings: PathMappings,
+    stop_on_entry: bool,
+    features: Vec<(String, String)>,
+    line_breakpoints: HashMap<String, Vec<LineBreakpoint>>,
+    function_breakpoints: Vec<String>,
+    exception_filters: Vec<String>,
+    breakpoints_generation: u64,
+    configured: bool,
+    engine: Option<Engine>,
+    queued: VecDeque<TcpStream>,
+    next_engine_id: u32,
+    process: Option<Arc<Mutex<Child>>>,
+    process_exited: bool,
+    pending: HashMap<u32, Pending>,
+    references: Vec<Reference>,
+}
+
+impl Session {
+    pub fn new(client: Client, events: Sender<Event>) -> Self {
+        Self {
+            client,
+            events,
+            mappings: PathMappings::new(None),
+            stop_on_entry: false,
+            features: Vec::new(),
+            line_breakpoints: HashMap::new(),
+            function_breakpoints: Vec::new(),
+            exception_filters: Vec::new(),
+            breakpoints_generation: 0,
+            configured: false,
+            engine: None,
+            queued: VecDeque::new(),
+            next_engine_id: 1,
+            process: None,
+            process_exited: false,
+            pending: HashMap::new(),
+            references: Vec::new(),
+        }
+    }
+
+    /// Handles an event, returning whether the session goes on.
+    pub fn handle(&mut self, event: Event) -> bool {
+        match event {
+            Event::Request(request) => return self.handle_request(request),
+            Event::ClientClosed => {
+                self.shut_down();
+                return false;
+            }
+            Event::EngineConnected(stream) => {
+                if self.engine.is_some() || !self.configured {
+                    self.queued.push_back(stream);
+                } else {
+                    self.start_engine(stream);
+                }
+            }
+            Event::Packet(id, packet) => {
+                if self.engine.as_ref().is_some_and(|engine| engine.id == id) {
+                    self.handle_packet(packet);
+                }
+            }
+            Event::EngineClosed(id) => {
+                if self.engine.as_ref().is_some_and(|engine| engine.id == id) {
+                    self.end_engine();
+                }
+            }
+            Event::Output(category, output) => self.client.output(category, output),
+            Event::ProcessExited(code) => {
+                self.process_exited = true;
+                self.client
+                    .event("exited", json!({ "exitCode": code.unwrap_or(1) }));
+                if self.engine.is_none() {
+                    self.client.event("terminated", json!({}));
+                }
+            }
+        }
+        true
+    }
+
+    fn handle_request(&mut self, request: Value) -> bool {
+        let arguments = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+        let result = match command {
+            "initialize" => {
+                let filters = EXCEPTION_FILTERS
+                    .iter()
+                    .map(|(filter, label)| json!({ "filter": filter, "label": label, "default": false }))
+                    .collect::<Vec<_>>();
+                Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsConditionalBreakpoints": true,
+                    "supportsHitConditionalBreakpoints": true,
+                    "supportsFunctionBreakpoints": true,
+                    "supportsEvaluateForHovers": true,
+                    "supportsTerminateRequest": true,
+                    "exceptionBreakpointFilters": filters,
+                }))
+            }
+            "launch" | "attach" => self.start(arguments).map(|()| json!({})),
+            "configurationDone" => {
+                self.configured = true;
+                if self.engine.is_none()
+                    && let Some(stream) = self.queued.pop_front()
+                {
+                    self.start_engine(stream);
+                }
+                Ok(json!({}))
+            }
+            "setBreakpoints" => Ok(self.set_breakpoints(arguments)),
+            "setFunctionBreakpoints" => {
+                self.function_breakpoints = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| Some(breakpoint["name"].as_str()?.to_string()))
+                    .collect();
+                self.breakpoints_changed();
+                let breakpoints = self
+                    .function_breakpoints
+                    .iter()
+                    .map(|_| json!({ "verified": true }))
+                    .collect::<Vec<_>>();
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "setExceptionBreakpoints" => {
+                self.exception_filters = arguments["filters"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|filter| Some(filter.as_str()?.to_string()))
+                    .collect();
+                self.breakpoints_changed();
+                Ok(json!({}))
+            }
+            "threads" => {
+                let threads = self
+                    .engine
+                    .iter()
+                    .map(|engine| json!({ "id": engine.id, "name": format!("Request {}", engine.id) }))
+                    .collect::<Vec<_>>();
+                Ok(json!({ "threads": threads }))
+            }
+            "stackTrace" => {
+                let pending = Pending::StackTrace(request.clone());
+                return self.send_for(&request, "stack_get", &[], None, pending);
+            }
+            "scopes" => {
+                let depth = arguments["frameId"].as_u64().unwrap_or_default();
+                let pending = Pending::Scopes {
+                    request: request.clone(),
+                    depth,
+                };
+                let options = [("d", depth.to_string())];
+                return self.send_for(&request, "context_names", &options, None, pending);
+            }
+            "variables" => return self.variables(&request),
+            "evaluate" => return self.evaluate(&request),
+            "continue" => self.resume("run", "breakpoint"),
+            "next" => self.resume("step_over", "step"),
+            "stepIn" => self.resume("step_into", "step"),
+            "stepOut" => self.resume("step_out", "step"),
+            "pause" => match &mut self.engine {
+                Some(engine) => {
+                    let transaction_id = engine.connection.send("break", &[], None);
+                    self.pending.insert(transaction_id, Pending::Ignore);
+                    Ok(json!({}))
+                }
+                None => Err("No script is connected".to_string()),
+            },
+            "terminate" => {
+                self.shut_down();
+                self.client.respond(&request, json!({}));
+                self.client.event("terminated", json!({}));
+                return true;
+            }
+            "disconnect" => {
+                self.shut_down();
+                self.client.respond(&request, json!({}));
+                return false;
+            }
+            command => Err(format!("Unsupported request: {command}")),
+        };
+
+        match result {
+            Ok(body) => {
+                self.client.respond(&request, body);
+                if matches!(command, "launch" | "attach") {
+                    self.client.event("initialized", json!({}));
+                }
+            }
+            Err(message) => self.client.fail(&request, &message),
+        }
+        true
+    }
+
+    /// Starts listening for engines and, for launch configurations with a
+    /// `program`, runs the script.
+    fn start(&mut self, arguments: &Value) -> Result<(), String> {
+        self.mappings = PathMappings::new(arguments.get("pathMappings"));
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or_default();
+        self.features = vec![("max_children".to_string(), MAX_CHILDREN.to_string())];
+        if let Some(settings) = arguments["xdebugSettings"].as_object() {
+            for (name, value) in settings {
+                let value = match value {
+                    Value::Bool(value) => u8::from(*value).to_string(),
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                self.features.retain(|(existing, _)| existing != name);
+                self.features.push((name.clone(), value));
+            }
+        }
+
+        let hostname = arguments["hostname"].as_str();
+        if hostname
+            .is_some_and(|hostname| hostname.starts_with("unix://") || hostname.starts_with(r"\\"))
+        {
+            return Err("The native adapter only listens on TCP ports".into());
+        }
+        let port = arguments["port"]
+            .as_u64()
+            .and_then(|port| u16::try_from(port).ok())
+            .unwrap_or(DEFAULT_PORT);
+        let listener = match hostname {
+            Some(hostname) => TcpListener::bind((hostname, port)),
+            None => {
+                TcpListener::bind(("::", port)).or_else(|_| TcpListener::bind(("0.0.0.0", port)))
+            }
+        }
+        .map_err(|error| format!("Could not listen for Xdebug on port {port}: {error}"))?;
+        let port = listener
+            .local_addr()
+            .map_err(|error| error.to_string())?
+            .port();
+
+        let events = self.events.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if events.send(Event::EngineConnected(stream)).is_err() {
+                    break;
+                }
+            }
+        });
+        self.client
+            .output("console", format!("Listening for Xdebug on port {port}\n"));
+
+        if let Some(program) = arguments["program"].as_str() {
+            self.launch(program, arguments, port)?;
+        }
+        Ok(())
+    }
+
+    fn launch(&mut self, program: &str, arguments: &Value, port: u16) -> Result<(), String> {
+        let strings = |value: &Value| {
+            value
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(|value| value.replace("${port}", &port.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let php = arguments["runtimeExecutable"].as_str().unwrap_or("php");
+        let mut command = Command::new(php);
+        command
+            .args(strings(&arguments["runtimeArgs"]))
+            .arg(program)
+            .args(strings(&arguments["args"]))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(cwd) = arguments["cwd"].as_str() {
+            command.current_dir(cwd);
+        }
+        for (key, value) in arguments["env"].as_object().into_iter().flatten() {
+            if let Some(value) = value.as_str() {
+                command.env(key, value.replace("${port}", &port.to_string()));
+            }
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|error| format!("Could not run {php}: {error}"))?;
+        if let Some(stdout) = child.stdout.take() {
+            self.forward_output("stdout", stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            self.forward_output("stderr", stderr);
+        }
+
+        let child = Arc::new(Mutex::new(child));
+        self.process = Some(child.clone());
+        let events = self.events.clone();
+        thread::spawn(move || {
+            loop {
+                let status = child.lock().map(|mut child| child.try_wait());
+                match status {
+                    Ok(Ok(None)) => thread::sleep(Duration::from_millis(50)),
+                    Ok(Ok(Some(status))) => {
+                        let _ = events.send(Event::ProcessExited(status.code()));
+                        break;
+                    }
+                    _ => {
+                        let _ = events.send(Event::ProcessExited(None));
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn forward_output(&self, category: &'static str, output: impl Read + Send + 'static) {
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(output);
+            let mut line = Vec::new();
+            while reader
+                .read_until(b'\n', &mut line)
+                .is_ok_and(|length| length > 0)
+            {
+                let text = String::from_utf8_lossy(&line).into_owned();
+                if events.send(Event::Output(category, text)).is_err() {
+                    break;
+                }
+                line.clear();
+            }
+        });
+    }
+
+    fn start_engine(&mut self, stream: TcpStream) {
+        let id = self.next_engine_id;
+        self.next_engine_id += 1;
+        let connection = Connection::new(stream);
+
+        let events = self.events.clone();
+        match connection.try_clone_reader() {
+            Ok(reader) => {
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(reader);
+                    while let Ok(Some(packet)) = dbgp::read_packet(&mut reader) {
+                        if events.send(Event::Packet(id, packet)).is_err() {
+                            return;
+                        }
+                    }
+                    let _ = events.send(Event::EngineClosed(id));
+                });
+            }
+            Err(_) => return,
+        }
+
+        self.engine = Some(Engine {
+            id,
+            connection,
+            running: true,
+            breakpoint_ids: Vec::new(),
+            breakpoints_generation: 0,
+        });
+        self.client
+            .event("thread", json!({ "reason": "started", "threadId": id }));
+    }
+
+    fn end_engine(&mut self) {
+        let Some(engine) = self.engine.take() else {
+            return;
+        };
+        engine.connection.close();
+        self.pending.clear();
+        self.references.clear();
+        self.client.event(
+            "thread",
+            json!({ "reason": "exited", "threadId": engine.id }),
+        );
+
+        if self.process.is_some() {
+            if self.process_exited {
+                self.client.event("terminated", json!({}));
+            }
+        } else if let Some(stream) = self.queued.pop_front() {
+            self.start_engine(stream);
+        }
+    }
+
+    /// Stops the script or lets it run to completion without the debugger.
+    fn shut_down(&mut self) {
+        if let Some(engine) = &mut self.engine {
+            let command = if self.process.is_some() {
+                "stop"
+            } else {
+                "detach"
+            };
+            engine.connection.send(command, &[], None);
+            engine.connection.close();
+        }
+        if let Some(process) = self.process.take()
+            && let Ok(mut child) = process.lock()
+        {
+            let _ = child.kill();
+        }
+    }
+
+    fn handle_packet(&mut self, packet: Element) {
+        match packet.local_name() {
+            "init" => self.initialize_engine(),
+            "stream" => {
+                let category = match packet.attribute("type") {
+                    Some("stderr") => "stderr",
+                    _ => "stdout",
+                };
+                self.client.output(category, decode_text(&packet));
+            }
+            "response" => {
+                let pending = packet
+                    .attribute("transaction_id")
+                    .and_then(|id| id.parse().ok())
+                    .and_then(|id| self.pending.remove(&id));
+                if let Some(pending) = pending {
+                    self.handle_response(packet, pending);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Configures a newly connected engine and sets it running.
+    fn initialize_engine(&mut self) {
+        for (name, value) in self.features.clone() {
+            self.send(
+                "feature_set",
+                &[("n", name), ("v", value)],
+                None,
+                Pending::Ignore,
+            );
+        }
+        self.sync_breakpoints();
+        let (command, reason) = if self.stop_on_entry {
+            ("step_into", "entry")
+        } else {
+            ("run", "breakpoint")
+        };
+        self.send(command, &[], None, Pending::Resume { reason });
+    }
+
+    fn handle_response(&mut self, response: Element, pending: Pending) {
+        if let Some(error) = response.child("error") {
+            let message = error
+                .child("message")
+                .map(|message| message.text.trim().to_string())
+                .unwrap_or_else(|| format!("Error {}", error.attribute("code").unwrap_or("?")));
+            match pending {
+                Pending::StackTrace(request)
+                | Pending::Scopes { request, .. }
+                | Pending::Variables { request, .. }
+                | Pending::Evaluate { request, .. } => self.client.fail(&request, &message),
+                _ => self
+                    .client
+                    .output("console", format!("Xdebug: {message}\n")),
+            }
+            return;
+        }
+
+        match pending {
+            Pending::Ignore => {}
+            Pending::BreakpointSet { generation } => {
+                let Some(id) = response.attribute("id").map(ToOwned::to_owned) else {
+                    return;
+                };
+                if let Some(engine) = &mut self.engine {
+                    if engine.breakpoints_generation == generation {
+                        engine.breakpoint_ids.push(id);
+                    } else {
+                        self.send("breakpoint_remove", &[("d", id)], None, Pending::Ignore);
+                    }
+                }
+            }
+            Pending::Resume { reason } => self.handle_resume(&response, reason),
+            Pending::StackTrace(request) => {
+                let frames = response
+                    .children("stack")
+                    .map(|frame| self.stack_frame(frame))
+                    .collect::<Vec<_>>();
+                let total = frames.len();
+                self.client.respond(
+                    &request,
+                    json!({ "stackFrames": frames, "totalFrames": total }),
+                );
+            }
+            Pending::Scopes { request, depth } => {
+                let scopes = response
+                    .children("context")
+                    .map(|context| {
+                        let id = context.attribute("id").and_then(|id| id.parse().ok());
+                        let name = context.attribute("name").unwrap_or("Variables");
+                        let reference = self.reference(Reference::Context {
+                            depth,
+                            context: id.unwrap_or_default(),
+                        });
+                        json!({
+                            "name": name,
+                            "variablesReference": reference,
+                            "expensive": name == "Superglobals",
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                self.client.respond(&request, json!({ "scopes": scopes }));
+            }
+            Pending::Variables {
+                request,
+                depth,
+                context,
+                property,
+            } => {
+                let properties = if property {
+                    response
+                        .child("property")
+                        .map(|property| property.children("property").collect())
+                        .unwrap_or_default()
+                } else {
+                    response.children("property").collect::<Vec<_>>()
+                };
+                let variables = properties
+                    .into_iter()
+                    .map(|property| self.variable(property, depth, context))
+                    .collect::<Vec<_>>();
+                self.client
+                    .respond(&request, json!({ "variables": variables }));
+            }
+            Pending::Evaluate { request, depth } => match response.child("property") {
+                Some(property) => {
+                    let variable = self.variable(property, depth, 0);
+                    self.client.respond(
+                        &request,
+                        json!({
+                            "result": variable["value"],
+                            "type": variable["type"],
+                            "variablesReference": variable["variablesReference"],
+                        }),
+                    );
+                }
+                None => self
+                    .client
+                    .respond(&request, json!({ "result": "", "variablesReference": 0 })),
+            },
+        }
+    }
+
+    fn handle_resume(&mut self, response: &Element, reason: &'static str) {
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+        match response.attribute("status") {
+            Some("break") => {
+                engine.running = false;
+                let thread_id = engine.id;
+                self.references.clear();
+                self.sync_breakpoints();
+
+                let message = response.child("message");
+                let mut body = json!({
+                    "reason": reason,
+                    "threadId": thread_id,
+                    "allThreadsStopped": true,
+                });
+                if let Some(message) = message
+                    && let Some(exception) = message.attribute("exception")
+                {
+                    body["reason"] = "exception".into();
+                    body["description"] = exception.into();
+                    body["text"] = format!("{exception}: {}", message.text.trim()).into();
+                }
+                self.client.event("stopped", body);
+            }
+            Some("stopping" | "stopped") => {
+                // The script has finished; let the engine end the connection.
+                let transaction_id = engine.connection.send("stop", &[], None);
+                self.pending.insert(transaction_id, Pending::Ignore);
+            }
+            _ => {}
+        }
+    }
+
+    fn resume(&mut self, command: &str, reason: &'static str) -> Result<Value, String> {
+        match &mut self.engine {
+            Some(engine) if !engine.running => {
+                engine.running = true;
+                let transaction_id = engine.connection.send(command, &[], None);
+                self.pending
+                    .insert(transaction_id, Pending::Resume { reason });
+                self.references.clear();
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            Some(_) => Err("The script is already running".into()),
+            None => Err("No script is connected".into()),
+        }
+    }
+
+    fn variables(&mut self, request: &Value) -> bool {
+        let reference = request["arguments"]["variablesReference"]
+            .as_u64()
+            .and_then(|reference| usize::try_from(reference).ok())
+            .and_then(|reference| self.references.get(reference.checked_sub(1)?));
+        let (command, options, depth, context, property) = match reference {
+            Some(Reference::Context { depth, context }) => (
+                "context_get",
+                vec![("d", depth.to_string()), ("c", context.to_string())],
+                *depth,
+                *context,
+                false,
+            ),
+            Some(Reference::Property {
+                depth,
+                context,
+                fullname,
+            }) => (
+                "property_get",
+                vec![
+                    ("d", depth.to_string()),
+                    ("c", context.to_string()),
+                    ("n", fullname.clone()),
+                ],
+                *depth,
+                *context,
+                true,
+            ),
+            Some(Reference::Values(values)) => {
+                let variables = values.clone();
+                self.client
+                    .respond(request, json!({ "variables": variables }));
+                return true;
+            }
+            None => {
+                self.client.fail(request, "Unknown variables reference");
+                return true;
+            }
+        };
+        let pending = Pending::Variables {
+            request: request.clone(),
+            depth,
+            context,
+            property,
+        };
+        self.send_for(request, command, &options, None, pending)
+    }
+
+    fn evaluate(&mut self, request: &Value) -> bool {
+        let arguments = &request["arguments"];
+        let expression = arguments["expression"].as_str().unwrap_or_default();
+        let depth = arguments["frameId"].as_u64().unwrap_or_default();
+        let pending = Pending::Evaluate {
+            request: request.clone(),
+            depth,
+        };
+
+        // Variables are read from the selected frame, which `eval` can't do.
+        if is_variable(expression) {
+            let options = [("d", depth.to_string()), ("n", expression.to_string())];
+            self.send_for(request, "property_get", &options, None, pending)
+        } else {
+            self.send_for(request, "eval", &[], Some(expression), pending)
+        }
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Value {
+        let path = arguments["source"]["path"].as_str().unwrap_or_default();
+        let breakpoints = arguments["breakpoints"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|breakpoint| {
+                Some(LineBreakpoint {
+                    line: breakpoint["line"].as_u64()?,
+                    condition: breakpoint["condition"].as_str().map(ToOwned::to_owned),
+                    hit_condition: breakpoint["hitCondition"].as_str().map(ToOwned::to_owned),
+                })
+            })
+            .collect::<Vec<_>>();
+        let body = breakpoints
+            .iter()
+            .map(|breakpoint| json!({ "verified": true, "line": breakpoint.line }))
+            .collect::<Vec<_>>();
+
+        self.line_breakpoints.insert(path.to_string(), breakpoints);
+        self.breakpoints_changed();
+        json!({ "breakpoints": body })
+    }
+
+    fn breakpoints_changed(&mut self) {
+        self.breakpoints_generation += 1;
+        if self.engine.as_ref().is_some_and(|engine| !engine.running) {
+            self.sync_breakpoints();
+        }
+    }
+
+    /// Replaces the engine's breakpoints with the current ones, unless they
+    /// are already up to date. The engine must not be running.
+    fn sync_breakpoints(&mut self) {
+        let generation = self.breakpoints_generation;
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+        if engine.breakpoints_generation == generation {
+            return;
+        }
+        engine.breakpoints_generation = generation;
+        for id in std::mem::take(&mut engine.breakpoint_ids) {
+            self.send("breakpoint_remove", &[("d", id)], None, Pending::Ignore);
+        }
+
+        let mut commands = Vec::new();
+        for (path, breakpoints) in &self.line_breakpoints {
+            let uri = dbgp::path_to_uri(&self.mappings.to_server(path));
+            for breakpoint in breakpoints {
+                let kind = if breakpoint.condition.is_some() {
+                    "conditional"
+                } else {
+                    "line"
+                };
+                let mut options = vec![
+                    ("t", kind.to_string()),
+                    ("f", uri.clone()),
+                    ("n", breakpoint.line.to_string()),
+                ];
+                options.extend(hit_condition_options(breakpoint.hit_condition.as_deref()));
+                commands.push((options, breakpoint.condition.clone()));
+            }
+        }
+        for function in &self.function_breakpoints {
+            commands.push((
+                vec![("t", "call".to_string()), ("m", function.clone())],
+                None,
+            ));
+        }
+        for filter in &self.exception_filters {
+            commands.push((
+                vec![("t", "exception".to_string()), ("x", filter.clone())],
+                None,
+            ));
+        }
+
+        for (options, condition) in commands {
+            let pending = Pending::BreakpointSet { generation };
+            self.send("breakpoint_set", &options, condition.as_deref(), pending);
+        }
+    }
+
+    fn stack_frame(&self, frame: &Element) -> Value {
+        let level = frame
+            .attribute("level")
+            .and_then(|level| level.parse::<u64>().ok());
+        let line = frame
+            .attribute("lineno")
+            .and_then(|line| line.parse::<u64>().ok());
+        let filename = frame.attribute("filename").unwrap_or_default();
+        let source = if filename.starts_with("file://") {
+            let path = self.mappings.to_local(&dbgp::uri_to_path(filename));
+            let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+            json!({ "name": name, "path": path })
+        } else {
+            // Code that doesn't come from a file, such as `eval`'d code.
+            json!({ "name": filename })
+        };
+        json!({
+            "id": level.unwrap_or_default(),
+            "name": frame.attribute("where").unwrap_or("{main}"),
+            "source": source,
+            "line": line.unwrap_or_default(),
+            "column": 1,
+        })
+    }
+
+    fn variable(&mut self, property: &Element, depth: u64, context: u64) -> Value {
+        let fullname = property.attribute("fullname");
+        let name = property.attribute("name").or(fullname).unwrap_or_default();
+        let kind = property.attribute("type").unwrap_or_default();
+        let has_children = property.attribute("children") == Some("1");
+
+        let reference = match fullname {
+            Some(fullname) if has_children => self.reference(Reference::Property {
+                depth,
+                context,
+                fullname: fullname.to_string(),
+            }),
+            None if has_children => {
+                let children = property
+                    .children("property")
+                    .map(|child| self.variable(child, depth, context))
+                    .collect();
+                self.reference(Reference::Values(children))
+            }
+            _ => 0,
+        };
+
+        json!({
+            "name": name,
+            "value": display_value(property),
+            "type": property.attribute("classname").unwrap_or(kind),
+            "variablesReference": reference,
+            "evaluateName": fullname,
+        })
+    }
+
+    fn reference(&mut self, reference: Reference) -> usize {
+        self.references.push(reference);
+        self.references.len()
+    }
+
+    /// Sends a command for a DAP request, failing the request if the engine
+    /// can't take commands. Returns whether the session goes on.
+    fn send_for(
+        &mut self,
+        request: &Value,
+        command: &str,
+        options: &[(&str, String)],
+        data: Option<&str>,
+        pending: Pending,
+    ) -> bool {
+        match &self.engine {
+            Some(engine) if !engine.running => self.send(command, options, data, pending),
+            Some(_) => self.client.fail(request, "The script is running"),
+            None => self.client.fail(request, "No script is connected"),
+        }
+        true
+    }
+
+    fn send(
+        &mut self,
+        command: &str,
+        options: &[(&str, String)],
+        data: Option<&str>,
+        pending: Pending,
+    ) {
+        if let Some(engine) = &mut self.engine {
+            let options = options
+                .iter()
+                .map(|(option, value)| (*option, value.as_str()))
+                .collect::<Vec<_>>();
+            let transaction_id = engine.connection.send(command, &options, data);
+            self.pending.insert(transaction_id, pending);
+        }
+    }
+}
+
+/// Translates a DAP hit condition such as `5`, `>= 5`, `== 5` or `% 5` into
+/// the options of `breakpoint_set`.
+fn hit_condition_options(hit_condition: Option<&str>) -> Vec<(&'static str, String)> {
+    let Some(hit_condition) = hit_condition.map(str::trim) else {
+        return Vec::new();
+    };
+    let (operator, value) = hit_condition.split_at(
+        hit_condition
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(hit_condition.len()),
+    );
+    let (operator, value) = match (operator.trim(), value) {
+        ("" | ">=", value) => (">=", value),
+        ("==" | "=", value) => ("==", value),
+        ("%", value) => ("%", value),
+        _ => return Vec::new(),
+    };
+    match value.trim().parse::<u64>() {
+        Ok(value) => vec![("h", value.to_string()), ("o", operator.to_string())],
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Returns whether an expression is a variable or a property or element of
+/// one, which can be read with `property_get`.
+fn is_variable(expression: &str) -> bool {
+    expression.starts_with('$')
+        && !expression.contains(|c: char| c.is_whitespace() || matches!(c, '(' | ';' | '='))
+}
+
+fn decode_text(element: &Element) -> String {
+    match element.attribute("encoding") {
+        Some("base64") => base64::decode(element.text.trim())
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default(),
+        _ => element.text.clone(),
+    }
+}
+
+fn display_value(property: &Element) -> String {
+    let value = decode_text(property);
+    match property.attribute("type").unwrap_or_default() {
+        "string" => format!("{value:?}"),
+        "bool" => if value == "1" { "true" } else { "false" }.to_string(),
+        "null" => "null".to_string(),
+        "array" => format!(
+            "array({})",
+            property.attribute("numchildren").unwrap_or("0")
+        ),
+        "object" => property
+            .attribute("classname")
+            .unwrap_or("object")
+            .to_string(),
+        "uninitialized" => "uninitialized".to_string(),
+        kind if value.is_empty() => kind.to_string(),
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use std::sync::mpsc::{self, Receiver};
+
+    use super::*;
+    use crate::{dap, xml};
+
+    /// Collects what the session sends to the editor.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A command the session sent to the fake engine.
+    #[derive(Debug)]
+    struct Command {
+        name: String,
+        transaction_id: String,
+        options: Vec<(String, String)>,
+        data: Option<String>,
+    }
+
+    impl Command {
+        fn option(&self, name: &str) -> Option<&str> {
+            self.options
+                .iter()
+                .find(|(option, _)| option == name)
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Drives a session with scripted DAP requests and DBGp packets, playing
+    /// both the editor and the engine.
+    struct Harness {
+        session: Session,
+        output: Output,
+        engine: Option<BufReader<TcpStream>>,
+        seq: u64,
+        _events: Receiver<Event>,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let (events, receiver) = mpsc::channel();
+            let output = Output::default();
+            Self {
+                session: Session::new(Client::new(Box::new(output.clone())), events),
+                output,
+                engine: None,
+                seq: 0,
+                _events: receiver,
+            }
+        }
+
+        /// Attaches with the given arguments, connects an engine and answers
+        /// the commands it gets until it's set running.
+        fn attach(arguments: Value) -> Self {
+            let mut harness = Self::new();
+            harness.request("initialize", json!({}));
+            let mut attach = json!({ "hostname": "127.0.0.1", "port": 0 });
+            attach
+                .as_object_mut()
+                .unwrap()
+                .extend(arguments.as_object().unwrap().clone());
+            let messages = harness.request("attach", attach);
+            assert_eq!(response(&messages, "attach")["success"], true);
+            harness
+        }
+
+        /// Sends a request and returns everything the session sent back.
+        fn request(&mut self, command: &str, arguments: Value) -> Vec<Value> {
+            self.seq += 1;
+            let request = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            assert!(self.session.handle(Event::Request(request)));
+            self.messages()
+        }
+
+        fn messages(&mut self) -> Vec<Value> {
+            let bytes = std::mem::take(&mut *self.output.0.lock().unwrap());
+            let mut reader = Cursor::new(bytes);
+            std::iter::from_fn(|| dap::read_message(&mut reader).unwrap()).collect()
+        }
+
+        /// Connects the fake engine and sends its `init` packet.
+        fn connect_engine(&mut self) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let engine = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            engine
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            self.engine = Some(BufReader::new(engine));
+            self.session.handle(Event::EngineConnected(stream));
+            self.packet(r#"<init fileuri="file:///var/www/index.php" language="PHP"/>"#);
+        }
+
+        fn packet(&mut self, xml: &str) -> Vec<Value> {
+            self.session
+                .handle(Event::Packet(1, xml::parse(xml).unwrap()));
+            self.messages()
+        }
+
+        /// Reads the next command the engine got and checks its name.
+        fn command(&mut self, name: &str) -> Command {
+            let command = self.next_command();
+            assert_eq!(command.name, name, "unexpected command {command:?}");
+            command
+        }
+
+        /// Answers a command as the engine.
+        fn reply(&mut self, command: &Command, attributes: &str, children: &str) -> Vec<Value> {
+            self.packet(&format!(
+                r#"<response xmlns:xdebug="https://xdebug.org/dbgp/xdebug" command="{}" transaction_id="{}" {attributes}>{children}</response>"#,
+                command.name, command.transaction_id
+            ))
+        }
+
+        /// Configures the session and connects an engine, answering its setup
+        /// commands until it runs.
+        fn start(&mut self) -> Command {
+            self.request("configurationDone", json!({}));
+            self.connect_engine();
+            self.command("feature_set");
+            let mut command = self.next_command();
+            while command.name == "breakpoint_set" {
+                command = self.next_command();
+            }
+            command
+        }
+
+        /// Reads the next command the engine got.
+        fn next_command(&mut self) -> Command {
+            let mut line = Vec::new();
+            self.engine
+                .as_mut()
+                .unwrap()
+                .read_until(0, &mut line)
+                .unwrap();
+            line.pop();
+            parse_command(&String::from_utf8(line).unwrap())
+        }
+
+        /// Runs into a breakpoint, leaving the engine stopped.
+        fn stopped() -> Self {
+            let mut harness = Self::attach(json!({ "pathMappings": { "/var/www": "/app" } }));
+            let run = harness.start();
+            assert_eq!(run.name, "run");
+            let messages = harness.reply(&run, r#"status="break" reason="ok""#, "");
+            assert_eq!(event(&messages, "stopped")["reason"], "breakpoint");
+            harness
+        }
+    }
+
+    fn parse_command(line: &str) -> Command {
+        let (line, data) = match line.split_once(" -- ") {
+            Some((line, data)) => (
+                line,
+                Some(String::from_utf8(base64::decode(data).unwrap()).unwrap()),
+            ),
+            None => (line, None),
+        };
+
+        let mut tokens = Vec::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' => {}
+                '"' => {
+                    let mut token = String::new();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '\\' => token.extend(chars.next()),
+                            '"' => break,
+                            c => token.push(c),
+                        }
+                    }
+                    tokens.push(token);
+                }
+                c => {
+                    let mut token = c.to_string();
+                    while let Some(c) = chars.next_if(|c| *c != ' ') {
+                        token.push(c);
+                    }
+                    tokens.push(token);
+                }
+            }
+        }
+
+        let mut tokens = tokens.into_iter();
+        let name = tokens.next().unwrap();
+        let mut options = Vec::new();
+        while let (Some(option), Some(value)) = (tokens.next(), tokens.next()) {
+            options.push((option.trim_start_matches('-').to_string(), value));
+        }
+        let transaction_id = options.remove(0).1;
+        Command {
+            name,
+            transaction_id,
+            options,
+            data,
+        }
+    }
+
+    fn response<'a>(messages: &'a [Value], command: &str) -> &'a Value {
+        messages
+            .iter()
+            .find(|message| message["type"] == "response" && message["command"] == command)
+            .unwrap_or_else(|| panic!("no response to {command} in {messages:#?}"))
+    }
+
+    fn event<'a>(messages: &'a [Value], name: &str) -> &'a Value {
+        &messages
+            .iter()
+            .find(|message| message["type"] == "event" && message["event"] == name)
+            .unwrap_or_else(|| panic!("no {name} event in {messages:#?}"))["body"]
+    }
+
+    #[test]
+    fn advertises_exception_filters() {
+        let mut harness = Harness::new();
+        let messages = harness.request("initialize", json!({}));
+        let body = &response(&messages, "initialize")["body"];
+        let filters = body["exceptionBreakpointFilters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|filter| filter["filter"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(filters, ["Notice", "Warning", "Error", "Exception", "*"]);
+        assert_eq!(body["supportsConditionalBreakpoints"], true);
+    }
+
+    #[test]
+    fn sets_breakpoints_when_the_engine_connects() {
+        let mut harness = Harness::attach(json!({ "pathMappings": { "/var/www": "/app" } }));
+        let messages = harness.request(
+            "setBreakpoints",
+            json!({
+                "source": { "path": "/app/src/index.php" },
+                "breakpoints": [
+                    { "line": 3 },
+                    { "line": 5, "condition": "$a > 1" },
+                    { "line": 7, "hitCondition": ">= 2" },
+                    { "line": 9, "hitCondition": "% 3" },
+                ],
+            }),
+        );
+        let breakpoints = &response(&messages, "setBreakpoints")["body"]["breakpoints"];
+        assert_eq!(breakpoints[1], json!({ "verified": true, "line": 5 }));
+        harness.request(
+            "setFunctionBreakpoints",
+            json!({ "breakpoints": [{ "name": "handle" }] }),
+        );
+        harness.request(
+            "setExceptionBreakpoints",
+            json!({ "filters": ["Exception"] }),
+        );
+        harness.request("configurationDone", json!({}));
+        harness.connect_engine();
+
+        let feature = harness.command("feature_set");
+        assert_eq!(feature.option("n"), Some("max_children"));
+
+        let line = harness.command("breakpoint_set");
+        assert_eq!(line.option("t"), Some("line"));
+        assert_eq!(line.option("f"), Some("file:///var/www/src/index.php"));
+        assert_eq!(line.option("n"), Some("3"));
+        assert_eq!(line.data, None);
+
+        let conditional = harness.command("breakpoint_set");
+        assert_eq!(conditional.option("t"), Some("conditional"));
+        assert_eq!(conditional.data.as_deref(), Some("$a > 1"));
+
+        let hit = harness.command("breakpoint_set");
+        assert_eq!((hit.option("h"), hit.option("o")), (Some("2"), Some(">=")));
+        let modulo = harness.command("breakpoint_set");
+        assert_eq!(
+            (modulo.option("h"), modulo.option("o")),
+            (Some("3"), Some("%"))
+        );
+
+        let function = harness.command("breakpoint_set");
+        assert_eq!(function.option("t"), Some("call"));
+        assert_eq!(function.option("m"), Some("handle"));
+
+        let exception = harness.command("breakpoint_set");
+        assert_eq!(exception.option("t"), Some("exception"));
+        assert_eq!(exception.option("x"), Some("Exception"));
+
+        harness.reply(&line, r#"id="101""#, "");
+        let run = harness.command("run");
+        let messages = harness.reply(&run, r#"status="break" reason="ok""#, "");
+        assert_eq!(event(&messages, "stopped")["reason"], "breakpoint");
+    }
+
+    #[test]
+    fn replaces_breakpoints_while_stopped() {
+        let mut harness = Harness::attach(json!({}));
+        harness.request(
+            "setExceptionBreakpoints",
+            json!({ "filters": ["Exception"] }),
+        );
+        harness.request("configurationDone", json!({}));
+        harness.connect_engine();
+        harness.command("feature_set");
+        let exception = harness.command("breakpoint_set");
+        harness.reply(&exception, r#"id="7""#, "");
+        let run = harness.command("run");
+        harness.reply(&run, r#"status="break" reason="ok""#, "");
+
+        harness.request(
+            "setExceptionBreakpoints",
+            json!({ "filters": ["Warning", "*"] }),
+        );
+        let remove = harness.command("breakpoint_remove");
+        assert_eq!(remove.option("d"), Some("7"));
+        let warning = harness.command("breakpoint_set");
+        assert_eq!(warning.option("x"), Some("Warning"));
+        let everything = harness.command("breakpoint_set");
+        assert_eq!(everything.option("x"), Some("*"));
+    }
+
+    #[test]
+    fn steps_and_reports_why_it_stopped() {
+        let mut harness = Harness::stopped();
+
+        for (request, command) in [
+            ("next", "step_over"),
+            ("stepIn", "step_into"),
+            ("stepOut", "step_out"),
+        ] {
+            let messages = harness.request(request, json!({ "threadId": 1 }));
+            assert_eq!(response(&messages, request)["success"], true);
+            let step = harness.command(command);
+            let messages = harness.reply(&step, r#"status="break" reason="ok""#, "");
+            assert_eq!(event(&messages, "stopped")["reason"], "step");
+        }
+
+        harness.request("continue", json!({ "threadId": 1 }));
+        let run = harness.command("run");
+        let messages = harness.request("next", json!({ "threadId": 1 }));
+        assert_eq!(response(&messages, "next")["success"], false);
+
+        let messages = harness.reply(
+            &run,
+            r#"status="break" reason="ok""#,
+            r#"<xdebug:message filename="file:///var/www/index.php" lineno="4" exception="RuntimeException" code="0"><![CDATA[Boom]]></xdebug:message>"#,
+        );
+        let stopped = event(&messages, "stopped");
+        assert_eq!(stopped["reason"], "exception");
+        assert_eq!(stopped["description"], "RuntimeException");
+        assert_eq!(stopped["text"], "RuntimeException: Boom");
+
+        harness.request("continue", json!({ "threadId": 1 }));
+        let run = harness.command("run");
+        harness.reply(&run, r#"status="stopping" reason="ok""#, "");
+        harness.command("stop");
+    }
+
+    #[test]
+    fn stops_on_entry() {
+        let mut harness = Harness::attach(json!({ "stopOnEntry": true }));
+        let step = harness.start();
+        assert_eq!(step.name, "step_into");
+        let messages = harness.reply(&step, r#"status="break" reason="ok""#, "");
+        assert_eq!(event(&messages, "stopped")["reason"], "entry");
+    }
+
+    #[test]
+    fn inspects_stack_scopes_and_variables() {
+        let mut harness = Harness::stopped();
+
+        harness.request("stackTrace", json!({ "threadId": 1 }));
+        let stack = harness.command("stack_get");
+        let messages = harness.reply(
+            &stack,
+            "",
+            r#"<stack where="handle" level="0" type="file" filename="file:///var/www/src/App.php" lineno="12"/>
+               <stack where="{main}" level="1" type="file" filename="file:///var/www/index.php" lineno="3"/>
+               <stack where="eval" level="2" type="eval" filename="dbgp://1" lineno="1"/>"#,
+        );
+        let body = &response(&messages, "stackTrace")["body"];
+        assert_eq!(body["totalFrames"], 3);
+        assert_eq!(
+            body["stackFrames"][0],
+            json!({
+                "id": 0,
+                "name": "handle",
+                "source": { "name": "App.php", "path": "/app/src/App.php" },
+                "line": 12,
+                "column": 1,
+            })
+        );
+        assert_eq!(
+            body["stackFrames"][2]["source"],
+            json!({ "name": "dbgp://1" })
+        );
+
+        harness.request("scopes", json!({ "frameId": 1 }));
+        let names = harness.command("context_names");
+        assert_eq!(names.option("d"), Some("1"));
+        let messages = harness.reply(
+            &names,
+            "",
+            r#"<context name="Locals" id="0"/><context name="Superglobals" id="1"/>"#,
+        );
+        let scopes = &response(&messages, "scopes")["body"]["scopes"];
+        assert_eq!(scopes[0]["name"], "Locals");
+        assert_eq!(scopes[1]["expensive"], true);
+        let locals = scopes[0]["variablesReference"].clone();
+
+        harness.request("variables", json!({ "variablesReference": locals }));
+        let context = harness.command("context_get");
+        assert_eq!(
+            (context.option("d"), context.option("c")),
+            (Some("1"), Some("0"))
+        );
+        let messages = harness.reply(
+            &context,
+            "",
+            r#"<property name="$name" fullname="$name" type="string" encoding="base64"><![CDATA[WmVk]]></property>
+               <property name="$items" fullname="$items" type="array" children="1" numchildren="2"/>
+               <property name="$flag" fullname="$flag" type="bool"><![CDATA[1]]></property>
+               <property name="$user" fullname="$user" type="object" classname="App\User" children="0"/>
+               <property name="$nothing" fullname="$nothing" type="null"/>"#,
+        );
+        let variables = &response(&messages, "variables")["body"]["variables"];
+        let values = variables
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variable| variable["value"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [r#""Zed""#, "array(2)", "true", r"App\User", "null"]
+        );
+        assert_eq!(variables[0]["variablesReference"], 0);
+        assert_eq!(variables[3]["type"], r"App\User");
+        let items = variables[1]["variablesReference"].clone();
+        assert_ne!(items, 0);
+
+        harness.request("variables", json!({ "variablesReference": items }));
+        let property = harness.command("property_get");
+        assert_eq!(property.option("n"), Some("$items"));
+        assert_eq!(property.option("d"), Some("1"));
+        let messages = harness.reply(
+            &property,
+            "",
+            r#"<property name="$items" fullname="$items" type="array" children="1" numchildren="2">
+                 <property name="0" fullname="$items[0]" type="int"><![CDATA[1]]></property>
+                 <property name="key" fullname="$items['key']" type="float"><![CDATA[2.5]]></property>
+               </property>"#,
+        );
+        let variables = &response(&messages, "variables")["body"]["variables"];
+        assert_eq!(variables[0]["name"], "0");
+        assert_eq!(variables[0]["value"], "1");
+        assert_eq!(variables[1]["evaluateName"], "$items['key']");
+
+        let messages = harness.request("variables", json!({ "variablesReference": 999 }));
+        assert_eq!(response(&messages, "variables")["success"], false);
+    }
+
+    #[test]
+    fn evaluates_variables_and_expressions() {
+        let mut harness = Harness::stopped();
+
+        harness.request(
+            "evaluate",
+            json!({ "expression": "$user->name", "frameId": 1 }),
+        );
+        let property = harness.command("property_get");
+        assert_eq!(property.option("n"), Some("$user->name"));
+        assert_eq!(property.option("d"), Some("1"));
+        let messages = harness.reply(
+            &property,
+            "",
+            r#"<property name="name" fullname="$user->name" type="string" encoding="base64"><![CDATA[WmVk]]></property>"#,
+        );
+        assert_eq!(
+            response(&messages, "evaluate")["body"]["result"],
+            r#""Zed""#
+        );
+
+        harness.request("evaluate", json!({ "expression": "strlen($name) + 1" }));
+        let eval = harness.command("eval");
+        assert_eq!(eval.data.as_deref(), Some("strlen($name) + 1"));
+        let messages = harness.reply(
+            &eval,
+            "",
+            r#"<property type="int"><![CDATA[4]]></property>"#,
+        );
+        let body = &response(&messages, "evaluate")["body"];
+        assert_eq!(
+            (&body["result"], &body["type"]),
+            (&json!("4"), &json!("int"))
+        );
+
+        // The children of an `eval` result can't be fetched again, so they
+        // are kept.
+        harness.request("evaluate", json!({ "expression": "[1]" }));
+        let eval = harness.command("eval");
+        let messages = harness.reply(
+            &eval,
+            "",
+            r#"<property type="array" children="1" numchildren="1"><property name="0" type="int"><![CDATA[1]]></property></property>"#,
+        );
+        let reference = response(&messages, "evaluate")["body"]["variablesReference"].clone();
+        let messages = harness.request("variables", json!({ "variablesReference": reference }));
+        assert_eq!(
+            response(&messages, "variables")["body"]["variables"][0]["value"],
+            "1"
+        );
+
+        harness.request("evaluate", json!({ "expression": "undefined()" }));
+        let eval = harness.command("eval");
+        let messages = harness.reply(
+            &eval,
+            "",
+            r#"<error code="206"><message><![CDATA[error evaluating code]]></message></error>"#,
+        );
+        let evaluate = response(&messages, "evaluate");
+        assert_eq!(evaluate["success"], false);
+        assert_eq!(evaluate["message"], "error evaluating code");
+    }
+
+    #[test]
+    fn fails_requests_without_a_stopped_engine() {
+        let mut harness = Harness::attach(json!({}));
+        let messages = harness.request("stackTrace", json!({ "threadId": 1 }));
+        let stack_trace = response(&messages, "stackTrace");
+        assert_eq!(stack_trace["success"], false);
+        assert_eq!(stack_trace["message"], "No script is connected");
+    }
+
+    #[test]
+    fn translates_hit_conditions() {
+        for (hit_condition, options) in [
+            ("5", vec![("h", "5"), ("o", ">=")]),
+            (">= 5", vec![("h", "5"), ("o", ">=")]),
+            ("==5", vec![("h", "5"), ("o", "==")]),
+            ("= 5", vec![("h", "5"), ("o", "==")]),
+            ("% 2", vec![("h", "2"), ("o", "%")]),
+            ("< 5", vec![]),
+            ("often", vec![]),
+        ] {
+            let expected = options
+                .into_iter()
+                .map(|(option, value)| (option, value.to_string()))
+                .collect::<Vec<_>>();
+            assert_eq!(hit_condition_options(Some(hit_condition)), expected);
+        }
+        assert!(hit_condition_options(None).is_empty());
+    }
+
+    #[test]
+    fn recognizes_variables() {
+        for expression in ["$a", "$user->name", "$items['key']", "$a[0][1]"] {
+            assert!(is_variable(expression), "{expression}");
+        }
+        for expression in ["strlen($a)", "$a + 1", "$a = 1", "count", "$a;"] {
+            assert!(!is_variable(expression), "{expression}");
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! A reader for the XML that DBGp engines send: elements, attributes, text,
+//! CDATA sections and the predefined and numeric entities.
+
+#[derive(Debug, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the first child with the given name, ignoring namespace
+    /// prefixes such as the one in `xdebug:message`.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children
+            .iter()
+            .find(|child| local_name(&child.name) == name)
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children
+            .iter()
+            .filter(move |child| local_name(&child.name) == name)
+    }
+
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+pub fn parse(source: &str) -> Result<Element, String> {
+    let mut parser = Parser {
+        source,
+        position: 0,
+    };
+    parser.skip_prolog();
+    let element = parser.element()?;
+    Ok(element)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skips the XML declaration, comments and doctype before the root.
+    fn skip_prolog(&mut self) {
+        loop {
+            self.skip_whitespace();
+            let end = if self.rest().starts_with("<?") {
+                self.rest().find("?>").map(|end| end + 2)
+            } else if self.rest().starts_with("<!--") {
+                self.rest().find("-->").map(|end| end + 3)
+            } else if self.rest().starts_with("<!") && !self.rest().starts_with("<![CDATA[") {
+                self.rest().find('>').map(|end| end + 1)
+            } else {
+                return;
+            };
+            match end {
+                Some(end) => self.position += end,
+                None => {
+                    self.position = self.source.len();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        if self.rest().starts_with(text) {
+            self.position += text.len();
+            Ok(())
+        } else {
+            Err(format!("expected {text:?} at byte {}", self.position))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '/'))
+            .unwrap_or(rest.len());
+        if length == 0 {
+            return Err(format!("expected a name at byte {}", self.position));
+        }
+        self.position += length;
+        Ok(rest[..length].to_string())
+    }
+
+    fn element(&mut self) -> Result<Element, String> {
+        self.expect("<")?;
+        let mut element = Element {
+            name: self.name()?,
+            ..Default::default()
+        };
+
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.position += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.position += 1;
+                break;
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = self
+                .rest()
+                .chars()
+                .next()
+                .filter(|c| matches!(c, '"' | '\''))
+                .ok_or_else(|| format!("expected a quoted value for {key:?}"))?;
+            self.position += 1;
+            let end = self
+                .rest()
+                .find(quote)
+                .ok_or_else(|| format!("unterminated value for {key:?}"))?;
+            let value = unescape(&self.rest()[..end]);
+            self.position += end + 1;
+            element.attributes.push((key, value));
+        }
+
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Err(format!("unterminated element <{}>", element.name));
+            }
+            if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let end = cdata.find("]]>").ok_or("unterminated CDATA section")?;
+                element.text.push_str(&cdata[..end]);
+                self.position += "<![CDATA[".len() + end + 3;
+            } else if rest.starts_with("<!--") {
+                let end = rest.find("-->").ok_or("unterminated comment")?;
+                self.position += end + 3;
+            } else if rest.starts_with("</") {
+                self.position += 2;
+                let name = self.name()?;
+                if name != element.name {
+                    return Err(format!("expected </{}>, found </{name}>", element.name));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(element);
+            } else if rest.starts_with('<') {
+                element.children.push(self.element()?);
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                element.text.push_str(&unescape(&rest[..end]));
+                self.position += end;
+            }
+        }
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_attributes_children_and_text() {
+        let element = parse(
+            r#"<?xml version="1.0" encoding="iso-8859-1"?>
+<!-- a comment -->
+<response xmlns:xdebug="https://xdebug.org/dbgp/xdebug" command="stack_get" transaction_id='7'>
+    <stack level="0" where="{main}"/>
+    <xdebug:message filename="file:///app/index.php">hello</xdebug:message>
+</response>"#,
+        )
+        .unwrap();
+
+        assert_eq!(element.local_name(), "response");
+        assert_eq!(element.attribute("command"), Some("stack_get"));
+        assert_eq!(element.attribute("transaction_id"), Some("7"));
+        assert_eq!(element.attribute("missing"), None);
+        assert_eq!(element.children("stack").count(), 1);
+        assert_eq!(
+            element.child("stack").unwrap().attribute("where"),
+            Some("{main}")
+        );
+        let message = element.child("message").unwrap();
+        assert_eq!(message.name, "xdebug:message");
+        assert_eq!(message.text, "hello");
+    }
+
+    #[test]
+    fn decodes_entities_and_cdata() {
+        let element = parse(
+            r#"<property name="$a&amp;b" value="&lt;&#65;&#x42;&gt;">x &quot;y&quot; &apos;z&apos; &unknown; <![CDATA[<raw> &amp;]]></property>"#,
+        )
+        .unwrap();
+
+        assert_eq!(element.attribute("name"), Some("$a&b"));
+        assert_eq!(element.attribute("value"), Some("<AB>"));
+        assert_eq!(element.text, r#"x "y" 'z' &unknown; <raw> &amp;"#);
+    }
+
+    #[test]
+    fn rejects_malformed_xml() {
+        for source in [
+            "<response>",
+            "<response></request>",
+            "<response attribute=unquoted/>",
+            "<response><![CDATA[unterminated</response>",
+            "",
+        ] {
+            assert!(parse(source).is_err(), "{source:?} should not parse");
+        }
+    }
+}
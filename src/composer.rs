@@ -0,0 +1,133 @@
+use zed_extension_api::{self as zed, serde_json::Value};
+
+/// The parts of a project's `composer.json` the extension cares about.
+pub(crate) struct ComposerJson {
+    json: Value,
+}
+
+impl ComposerJson {
+    /// Reads `composer.json` from the root of the worktree, if there is one.
+    pub(crate) fn read(worktree: &zed::Worktree) -> Option<Self> {
+        let contents = worktree.read_text_file("composer.json").ok()?;
+        let json = zed::serde_json::from_str(&contents).ok()?;
+        Some(Self { json })
+    }
+
+    /// Returns the PHP version the project targets as `major.minor.patch`.
+    ///
+    /// `config.platform.php` is the version Composer resolves dependencies
+    /// for, so it wins over the lowest version allowed by `require.php`.
+    pub(crate) fn php_version(&self) -> Option<String> {
+        let platform_php = self
+            .json
+            .pointer("/config/platform/php")
+            .and_then(Value::as_str);
+        let (major, minor, patch) = platform_php
+            .and_then(parse_version)
+            .or_else(|| lowest_version(self.json.pointer("/require/php")?.as_str()?))?;
+        Some(format!("{major}.{minor}.{patch}"))
+    }
+
+    /// Returns whether the project requires `package` in `require` or
+    /// `require-dev`.
+    pub(crate) fn requires(&self, package: &str) -> bool {
+        ["require", "require-dev"].into_iter().any(|section| {
+            self.json
+                .get(section)
+                .and_then(|packages| packages.get(package))
+                .is_some()
+        })
+    }
+
+    /// Returns the PHP extensions required through `ext-*` packages in
+    /// `require` and `require-dev`, e.g. `redis` for `ext-redis`.
+    pub(crate) fn required_extensions(&self) -> Vec<String> {
+        ["require", "require-dev"]
+            .into_iter()
+            .filter_map(|section| self.json.get(section)?.as_object())
+            .flat_map(|packages| packages.keys())
+            .filter_map(|package| package.strip_prefix("ext-"))
+            .map(ToOwned::to_owned)
+            .collect()
+    }
+}
+
+/// Returns the lowest version a Composer constraint such as `^8.1`,
+/// `>=7.4 <8.3` or `^7.4 || ^8.0` allows.
+fn lowest_version(constraint: &str) -> Option<(u32, u32, u32)> {
+    constraint
+        .split('|')
+        .filter_map(|alternative| {
+            // The lower bound comes first within an alternative.
+            let start = alternative.find(|c: char| c.is_ascii_digit())?;
+            parse_version(&alternative[start..])
+        })
+        .min()
+}
+
+/// Parses the leading `major[.minor[.patch]]` of a version string.
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let version = version.trim().trim_start_matches('v');
+    let end = version
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(version.len());
+    let mut parts = version[..end]
+        .split('.')
+        .map_while(|part| part.parse::<u32>().ok());
+
+    let major = parts.next()?;
+    Some((major, parts.next().unwrap_or(0), parts.next().unwrap_or(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use zed_extension_api::serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn finds_the_lowest_allowed_version() {
+        for (constraint, expected) in [
+            ("^8.1", Some((8, 1, 0))),
+            ("~7.4.3", Some((7, 4, 3))),
+            (">=7.4 <8.3", Some((7, 4, 0))),
+            ("^7.4 || ^8.0", Some((7, 4, 0))),
+            ("^8.0|^7.2", Some((7, 2, 0))),
+            ("8.2.*", Some((8, 2, 0))),
+            ("*", None),
+        ] {
+            assert_eq!(lowest_version(constraint), expected, "{constraint}");
+        }
+    }
+
+    #[test]
+    fn prefers_the_platform_version() {
+        for (json, expected) in [
+            (json!({ "require": { "php": "^8.1" } }), Some("8.1.0")),
+            (
+                json!({
+                    "require": { "php": "^8.1" },
+                    "config": { "platform": { "php": "8.2.12" } },
+                }),
+                Some("8.2.12"),
+            ),
+            (json!({ "require": { "laravel/framework": "^11.0" } }), None),
+        ] {
+            let composer = ComposerJson { json };
+            assert_eq!(composer.php_version().as_deref(), expected);
+        }
+    }
+
+    #[test]
+    fn lists_required_extensions() {
+        let composer = ComposerJson {
+            json: json!({
+                "require": { "php": "^8.1", "ext-redis": "*" },
+                "require-dev": { "ext-xdebug": "*", "phpunit/phpunit": "^11" },
+            }),
+        };
+        assert_eq!(composer.required_extensions(), ["redis", "xdebug"]);
+        assert!(composer.requires("phpunit/phpunit"));
+        assert!(!composer.requires("pestphp/pest"));
+    }
+}
@@ -0,0 +1,31 @@
+//! The debug adapters this extension provides, and what they share.
+
+mod native;
+
+use zed_extension_api::{
+    DebugAdapterBinary, DebugConfig, DebugScenario, DebugTaskDefinition,
+    StartDebuggingRequestArgumentsRequest, Worktree, serde_json::Value,
+};
+
+pub(crate) use native::NativeAdapter;
+
+/// A debug adapter registered under `[debug_adapters.<name>]` in
+/// `extension.toml`.
+pub(crate) trait DebugAdapter: Send + Sync {
+    /// The name the adapter is registered under.
+    fn name(&self) -> &'static str;
+
+    fn dap_request_kind(
+        &self,
+        config: &Value,
+    ) -> Result<StartDebuggingRequestArgumentsRequest, String>;
+
+    fn dap_config_to_scenario(&self, config: DebugConfig) -> Result<DebugScenario, String>;
+
+    fn get_binary(
+        &mut self,
+        config: DebugTaskDefinition,
+        user_provided_debug_adapter_path: Option<String>,
+        worktree: &Worktree,
+    ) -> Result<DebugAdapterBinary, String>;
+}
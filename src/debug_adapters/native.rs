@@ -0,0 +1,55 @@
+//! Installation of `php-dbgp-adapter`, the adapter from this repository's
+//! `crates/php-dbgp-adapter`. It talks DBGp to Xdebug without Node.js, and
+//! drives phpdbg with `--phpdbg`.
+
+use std::fs;
+
+use zed_extension_api as zed;
+
+use crate::install::{InstallDir, asset_name, check_executable};
+
+const REPO: &str = "zed-extensions/php";
+const BINARY_NAME: &str = "php-dbgp-adapter";
+const INSTALL_DIR: InstallDir = InstallDir::new(BINARY_NAME);
+/// The adapter is released together with the extension, so always use the
+/// build from the extension's own release.
+const RELEASE_TAG: &str = concat!("v", env!("CARGO_PKG_VERSION"));
+
+pub(crate) struct NativeAdapter {
+    cached_binary_path: Option<String>,
+}
+
+impl NativeAdapter {
+    pub(crate) fn new() -> Self {
+        Self {
+            cached_binary_path: None,
+        }
+    }
+
+    pub(crate) fn binary_path(&mut self, worktree: &zed::Worktree) -> Result<String, String> {
+        if let Some(path) = worktree.which(BINARY_NAME) {
+            return Ok(path);
+        }
+        if let Some(path) = &self.cached_binary_path
+            && fs::metadata(path).is_ok_and(|stat| stat.is_file())
+        {
+            return Ok(path.clone());
+        }
+
+        let (platform, arch) = zed::current_platform();
+        let binary_file = match platform {
+            zed::Os::Windows => format!("{BINARY_NAME}.exe"),
+            _ => BINARY_NAME.to_string(),
+        };
+        let binary_path = INSTALL_DIR.ensure_release(
+            None,
+            REPO,
+            Some(RELEASE_TAG),
+            &binary_file,
+            || asset_name(BINARY_NAME, platform, arch),
+            check_executable,
+        )?;
+        self.cached_binary_path = Some(binary_path.clone());
+        Ok(binary_path)
+    }
+}
@@ -0,0 +1,104 @@
+//! Detection of the frameworks a PHP project is built on.
+//!
+//! Each language server applies its own presets for the detected frameworks.
+//! Users can turn a preset on or off per server through
+//! `lsp.<server>.settings.frameworks`, e.g. `{ "wordpress": false }`.
+
+use zed_extension_api::settings::LspSettings;
+use zed_extension_api::{self as zed, serde_json::Value};
+
+use crate::composer::ComposerJson;
+
+/// The key in `lsp.<server>.settings` that overrides framework detection.
+pub(crate) const FRAMEWORKS_SETTING: &str = "frameworks";
+
+/// PHP files with extensions other than `.php` that Drupal modules and themes use.
+pub(crate) const DRUPAL_FILE_EXTENSIONS: &[&str] =
+    &["module", "inc", "install", "theme", "profile"];
+
+/// IDE helper files generated by `barryvdh/laravel-ide-helper`.
+pub(crate) const LARAVEL_IDE_HELPER_FILES: &[&str] = &[
+    "_ide_helper.php",
+    "_ide_helper_models.php",
+    ".phpstorm.meta.php",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Framework {
+    Laravel,
+    Symfony,
+    WordPress,
+    Drupal,
+}
+
+impl Framework {
+    const ALL: [Self; 4] = [Self::Laravel, Self::Symfony, Self::WordPress, Self::Drupal];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Laravel => "laravel",
+            Self::Symfony => "symfony",
+            Self::WordPress => "wordpress",
+            Self::Drupal => "drupal",
+        }
+    }
+
+    fn is_detected(self, worktree: &zed::Worktree, composer: Option<&ComposerJson>) -> bool {
+        let has_file = |path: &str| worktree.read_text_file(path).is_ok();
+        let requires = |package: &str| composer.is_some_and(|composer| composer.requires(package));
+
+        match self {
+            Self::Laravel => requires("laravel/framework") || has_file("artisan"),
+            Self::Symfony => requires("symfony/framework-bundle") || has_file("symfony.lock"),
+            Self::WordPress => {
+                requires("johnpbloch/wordpress")
+                    || requires("roots/wordpress")
+                    || has_file("wp-config.php")
+                    || has_file("wp-includes/version.php")
+            }
+            Self::Drupal => {
+                requires("drupal/core")
+                    || requires("drupal/core-recommended")
+                    || has_file("core/lib/Drupal.php")
+                    || has_file("web/core/lib/Drupal.php")
+            }
+        }
+    }
+}
+
+/// Returns the frameworks whose presets the given language server applies:
+/// the detected ones, adjusted by `lsp.<server>.settings.frameworks`.
+pub(crate) fn frameworks(
+    language_server_name: &str,
+    worktree: &zed::Worktree,
+    composer: Option<&ComposerJson>,
+) -> Vec<Framework> {
+    let overrides = LspSettings::for_worktree(language_server_name, worktree)
+        .ok()
+        .and_then(|lsp_settings| lsp_settings.settings)
+        .and_then(|mut settings| settings.get_mut(FRAMEWORKS_SETTING).map(Value::take))
+        .unwrap_or_default();
+
+    let frameworks = Framework::ALL
+        .into_iter()
+        .filter(|framework| {
+            overrides
+                .get(framework.name())
+                .and_then(Value::as_bool)
+                .unwrap_or_else(|| framework.is_detected(worktree, composer))
+        })
+        .collect::<Vec<_>>();
+
+    if !frameworks.is_empty() {
+        let names = frameworks
+            .iter()
+            .map(|framework| framework.name())
+            .collect::<Vec<_>>();
+        eprintln!(
+            "{language_server_name}: applying framework presets: {}",
+            names.join(", ")
+        );
+    }
+
+    frameworks
+}
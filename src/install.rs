@@ -0,0 +1,700 @@
+//! Layout of the extension's working directory.
+//!
+//! Every tool this extension installs shares one working directory, so each of
+//! them owns a namespaced directory (`<tool>/<tool>_<version>`) and cleanup only
+//! ever removes older versions of that same tool. npm packages are the exception:
+//! Zed installs them into a shared `node_modules` that npm manages by itself.
+//!
+//! Users can pin a tool to a specific version, in which case the installers
+//! install exactly that version and never look for a newer one.
+//!
+//! A freshly downloaded version is smoke-tested before the older ones go away.
+//! If it doesn't work, it's marked broken so it isn't downloaded again, and
+//! the installer rolls back to the last version that did. The extension may
+//! only run the commands that `extension.toml` grants, which don't include
+//! anything it downloads, so smoke tests check the downloaded files instead.
+
+use std::{env, fs, path::Path};
+
+use zed_extension_api::settings::LspSettings;
+use zed_extension_api::{self as zed, LanguageServerId, Result};
+
+use crate::version::{self, Version};
+
+/// Marks a version directory whose install failed its smoke test.
+const BROKEN_MARKER: &str = ".broken";
+
+/// The key in `lsp.<server>.settings` that pins the server to a version.
+pub(crate) const VERSION_SETTING: &str = "version";
+
+/// Returns the version the given language server is pinned to through
+/// `lsp.<server>.settings.version`, if any.
+pub(crate) fn pinned_version(
+    language_server_name: &str,
+    worktree: &zed::Worktree,
+) -> Option<String> {
+    LspSettings::for_worktree(language_server_name, worktree)
+        .ok()?
+        .settings?
+        .get(VERSION_SETTING)?
+        .as_str()
+        .map(ToOwned::to_owned)
+}
+
+/// Fetches the release to install: the pinned tag if there is one, the latest
+/// release otherwise. A pinned tag that doesn't exist is retried with the other
+/// spelling, so `0.5.0` finds the release tagged `v0.5.0` and vice versa.
+pub(crate) fn github_release(repo: &str, pinned_tag: Option<&str>) -> Result<zed::GithubRelease> {
+    match pinned_tag {
+        Some(tag) => zed::github_release_by_tag_name(repo, tag).or_else(|error| {
+            zed::github_release_by_tag_name(repo, &other_tag_spelling(tag)).map_err(|_| error)
+        }),
+        None => zed::latest_github_release(
+            repo,
+            zed::GithubReleaseOptions {
+                require_assets: true,
+                pre_release: false,
+            },
+        ),
+    }
+}
+
+/// Returns the name and type of the release asset of a Rust binary for the
+/// given platform, which is named after its target triple:
+/// `<name>-<arch>-<target>.tar.gz`, or `.zip` on Windows.
+pub(crate) fn asset_name(
+    name: &str,
+    platform: zed::Os,
+    arch: zed::Architecture,
+) -> Result<(String, zed::DownloadedFileType)> {
+    let (target, file_type, extension) = match platform {
+        zed::Os::Mac => ("apple-darwin", zed::DownloadedFileType::GzipTar, "tar.gz"),
+        zed::Os::Linux => (
+            "unknown-linux-gnu",
+            zed::DownloadedFileType::GzipTar,
+            "tar.gz",
+        ),
+        zed::Os::Windows => ("pc-windows-msvc", zed::DownloadedFileType::Zip, "zip"),
+    };
+    let arch = match arch {
+        zed::Architecture::Aarch64 => "aarch64",
+        zed::Architecture::X8664 => "x86_64",
+        _ => return Err(format!("unsupported architecture: {arch:?}")),
+    };
+    Ok((format!("{name}-{arch}-{target}.{extension}"), file_type))
+}
+
+/// Returns `tag` with a `v` prefix if it has none, and without it otherwise.
+fn other_tag_spelling(tag: &str) -> String {
+    match tag.strip_prefix('v') {
+        Some(version) => version.to_string(),
+        None => format!("v{tag}"),
+    }
+}
+
+pub(crate) struct InstallDir {
+    name: &'static str,
+}
+
+impl InstallDir {
+    pub(crate) const fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+
+    /// Returns the directory holding the given version of the tool.
+    pub(crate) fn version_dir(&self, version: &str) -> String {
+        format!("{0}/{0}_{version}", self.name)
+    }
+
+    /// Creates (if needed) and returns the directory for the given version.
+    pub(crate) fn create_version_dir(&self, version: &str) -> Result<String> {
+        let version_dir = self.version_dir(version);
+        fs::create_dir_all(&version_dir)
+            .map_err(|e| format!("failed to create directory {version_dir}: {e}"))?;
+        Ok(version_dir)
+    }
+
+    /// Lists the versions of the tool that currently have a directory on disk.
+    pub(crate) fn installed_versions(&self) -> Vec<String> {
+        let prefix = format!("{}_", self.name);
+        fs::read_dir(self.name)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                if !entry.file_type().ok()?.is_dir() {
+                    return None;
+                }
+                entry
+                    .file_name()
+                    .to_str()?
+                    .strip_prefix(&prefix)
+                    .map(ToOwned::to_owned)
+            })
+            .collect()
+    }
+
+    /// Lists the installed versions whose directory contains `file` and that
+    /// aren't marked broken.
+    fn complete_versions(&self, file: &str) -> impl Iterator<Item = String> {
+        self.installed_versions()
+            .into_iter()
+            .filter(move |version| {
+                fs::metadata(format!("{}/{file}", self.version_dir(version)))
+                    .is_ok_and(|stat| stat.is_file())
+                    && !self.is_broken(version)
+            })
+    }
+
+    /// Returns whether the given version failed its smoke test.
+    pub(crate) fn is_broken(&self, version: &str) -> bool {
+        Path::new(&self.version_dir(version))
+            .join(BROKEN_MARKER)
+            .is_file()
+    }
+
+    /// Returns the path of `file` in an installed release of `repo`, which is
+    /// the pinned version if there is one and the latest one otherwise.
+    ///
+    /// A release that isn't installed yet is downloaded from the asset that
+    /// `asset` names, and smoke-tested like `verify_install` describes. If
+    /// there is no pin and the latest release can't be fetched, the newest
+    /// installed version is used instead. The progress is reported as the
+    /// installation status of `language_server_id`, if any.
+    pub(crate) fn ensure_release(
+        &self,
+        language_server_id: Option<&LanguageServerId>,
+        repo: &str,
+        pinned_version: Option<&str>,
+        file: &str,
+        asset: impl FnOnce() -> Result<(String, zed::DownloadedFileType)>,
+        smoke_test: impl FnOnce(&str) -> Result<()>,
+    ) -> Result<String> {
+        let set_status = |status| {
+            if let Some(language_server_id) = language_server_id {
+                zed::set_language_server_installation_status(language_server_id, &status);
+            }
+        };
+
+        if let Some(version) =
+            pinned_version.and_then(|version| self.installed_version(version, file))
+        {
+            return Ok(format!("{}/{file}", self.version_dir(&version)));
+        }
+
+        set_status(zed::LanguageServerInstallationStatus::CheckingForUpdate);
+        let release = match github_release(repo, pinned_version) {
+            Ok(release) => release,
+            Err(error) if pinned_version.is_none() => {
+                let Some(version) = self.latest_installed_version(file) else {
+                    return Err(error);
+                };
+                report_offline_fallback(self.name, &version, &error);
+                return Ok(format!("{}/{file}", self.version_dir(&version)));
+            }
+            Err(error) => return Err(error),
+        };
+
+        let (asset_name, file_type) = asset()?;
+        let asset = release
+            .assets
+            .iter()
+            .find(|asset| asset.name == asset_name)
+            .ok_or_else(|| {
+                format!(
+                    "{repo} {} has no release asset {asset_name:?} for this platform; put {} \
+                     on your PATH instead",
+                    release.version, self.name
+                )
+            })?;
+
+        self.remove_legacy_versions();
+        let version_dir = self.create_version_dir(&release.version)?;
+        let path = format!("{version_dir}/{file}");
+        let is_downloaded = fs::metadata(&path).is_ok_and(|stat| stat.is_file());
+        if !is_downloaded {
+            set_status(zed::LanguageServerInstallationStatus::Downloading);
+            // Archives are extracted into the directory, anything else is the file.
+            let destination = match file_type {
+                zed::DownloadedFileType::GzipTar | zed::DownloadedFileType::Zip => &version_dir,
+                zed::DownloadedFileType::Gzip | zed::DownloadedFileType::Uncompressed => &path,
+            };
+            zed::download_file(&asset.download_url, destination, file_type)
+                .map_err(|e| format!("failed to download file: {e}"))?;
+            zed::make_file_executable(&path)?;
+        }
+        if is_downloaded && !self.is_broken(&release.version) {
+            return Ok(path);
+        }
+
+        let version = self.verify_install(
+            language_server_id,
+            &release.version,
+            pinned_version.is_some(),
+            file,
+            smoke_test,
+        )?;
+        Ok(format!("{}/{file}", self.version_dir(&version)))
+    }
+
+    /// Decides which version to use after `version` was downloaded, or turned
+    /// out to be one that is marked broken, and returns it.
+    ///
+    /// A new version is checked with `smoke_test`, which gets the absolute path
+    /// of `file`. If it works, it replaces the older versions, except for the
+    /// previous one, which is kept as the last known-good version. If it
+    /// doesn't, it's marked broken and the newest working version is used
+    /// instead, or the newest older one if `version` is pinned; the failure is
+    /// reported as the installation status of `language_server_id`, if any.
+    pub(crate) fn verify_install(
+        &self,
+        language_server_id: Option<&LanguageServerId>,
+        version: &str,
+        is_pinned: bool,
+        file: &str,
+        smoke_test: impl FnOnce(&str) -> Result<()>,
+    ) -> Result<String> {
+        let error = if self.is_broken(version) {
+            "it failed its smoke test when it was installed".to_string()
+        } else {
+            let path = format!("{}/{file}", self.version_dir(version));
+            let path = env::current_dir()
+                .map(|dir| dir.join(&path).to_string_lossy().into_owned())
+                .unwrap_or(path);
+            match smoke_test(&path) {
+                Ok(()) => {
+                    report_skipped_run(self.name, version);
+                    let previous = last_known_good(self.complete_versions(file), version);
+                    self.remove_older_versions(previous.as_deref().unwrap_or(version));
+                    return Ok(version.to_string());
+                }
+                Err(error) => {
+                    fs::write(
+                        Path::new(&self.version_dir(version)).join(BROKEN_MARKER),
+                        &error,
+                    )
+                    .ok();
+                    error
+                }
+            }
+        };
+
+        let Some(previous) = fallback_version(self.complete_versions(file), version, is_pinned)
+        else {
+            return Err(format!(
+                "{} {version} does not work ({error}) and there is no earlier version to fall \
+                 back to",
+                self.name
+            ));
+        };
+        report_fallback(self.name, language_server_id, version, &error, &previous);
+        Ok(previous)
+    }
+
+    /// Returns the newest installed version whose directory contains `file`.
+    pub(crate) fn latest_installed_version(&self, file: &str) -> Option<String> {
+        self.complete_versions(file)
+            .max_by(|a, b| version::compare(a, b))
+    }
+
+    /// Returns the installed version that is the same as `version` (which may
+    /// be spelled differently, e.g. `v1.2.0` for `1.2.0`) and whose directory
+    /// contains `file`.
+    pub(crate) fn installed_version(&self, version: &str, file: &str) -> Option<String> {
+        self.complete_versions(file)
+            .find(|installed| version::same(installed, version))
+    }
+
+    /// Removes the `<tool>-<version>` directories that versions before the
+    /// namespaced layout installed into the root of the working directory.
+    /// Nothing else cleans them up; once they're gone, this finds nothing.
+    pub(crate) fn remove_legacy_versions(&self) {
+        let prefix = format!("{}-", self.name);
+        for entry in fs::read_dir(".").into_iter().flatten().flatten() {
+            let is_legacy = entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+                && entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_prefix(&prefix))
+                    .is_some_and(|version| Version::parse(version).is_some());
+            if is_legacy {
+                fs::remove_dir_all(entry.path()).ok();
+            }
+        }
+    }
+
+    /// Removes every installed version of the tool that is older than `keep`.
+    /// Newer versions stay, so pinning an older release doesn't throw away the
+    /// newest build.
+    pub(crate) fn remove_older_versions(&self, keep: &str) {
+        for version in self.installed_versions() {
+            if version::compare(&version, keep).is_lt() {
+                fs::remove_dir_all(self.version_dir(&version)).ok();
+            }
+        }
+    }
+}
+
+pub(crate) struct NpmPackage {
+    name: &'static str,
+}
+
+impl NpmPackage {
+    pub(crate) const fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+
+    /// Installs the pinned (or else the latest) version of the package into
+    /// `node_modules`, unless it is already installed, and checks that it
+    /// provides `server_path`.
+    ///
+    /// A new version is checked with `smoke_test`, which gets the absolute path
+    /// of `server_path`. If it doesn't work, it's recorded as broken and the
+    /// previously installed version is reinstalled, unless the package is
+    /// pinned and that version is newer.
+    pub(crate) fn install(
+        &self,
+        language_server_id: &LanguageServerId,
+        server_path: &str,
+        pinned_version: Option<&str>,
+        smoke_test: impl FnOnce(&str) -> Result<()>,
+    ) -> Result<()> {
+        let server_exists = || fs::metadata(server_path).is_ok_and(|stat| stat.is_file());
+
+        let version = if let Some(version) = pinned_version {
+            version.to_string()
+        } else {
+            zed::set_language_server_installation_status(
+                language_server_id,
+                &zed::LanguageServerInstallationStatus::CheckingForUpdate,
+            );
+            match zed::npm_package_latest_version(self.name) {
+                Ok(version) => version,
+                Err(error) if server_exists() => {
+                    let installed_version = zed::npm_package_installed_version(self.name)
+                        .ok()
+                        .flatten()
+                        .unwrap_or_else(|| "unknown".to_string());
+                    report_offline_fallback(self.name, &installed_version, &error);
+                    return Ok(());
+                }
+                Err(error) => return Err(error),
+            }
+        };
+
+        let installed_version =
+            zed::npm_package_installed_version(self.name)?.filter(|_| server_exists());
+        let is_broken = self.is_broken(&version);
+        if !is_broken
+            && installed_version
+                .as_deref()
+                .is_some_and(|installed| version::same(installed, &version))
+        {
+            return Ok(());
+        }
+
+        let error = if is_broken {
+            "it failed its smoke test when it was installed".to_string()
+        } else {
+            zed::set_language_server_installation_status(
+                language_server_id,
+                &zed::LanguageServerInstallationStatus::Downloading,
+            );
+            match zed::npm_install_package(self.name, &version) {
+                Ok(()) => {}
+                // A pinned version is never replaced by whatever is installed.
+                Err(error) if pinned_version.is_none() && server_exists() => {
+                    let installed_version = installed_version.as_deref().unwrap_or("unknown");
+                    report_offline_fallback(self.name, installed_version, &error);
+                    return Ok(());
+                }
+                Err(error) => return Err(error),
+            }
+            if !server_exists() {
+                Err(format!(
+                    "installed package '{}' did not contain expected path '{server_path}'",
+                    self.name
+                ))?;
+            }
+
+            let path = env::current_dir()
+                .map(|dir| dir.join(server_path).to_string_lossy().into_owned())
+                .unwrap_or_else(|_| server_path.to_string());
+            match smoke_test(&path) {
+                Ok(()) => {
+                    report_skipped_run(self.name, &version);
+                    fs::remove_file(self.broken_marker()).ok();
+                    return Ok(());
+                }
+                Err(error) => {
+                    fs::write(self.broken_marker(), &version).ok();
+                    error
+                }
+            }
+        };
+
+        let Some(previous) = fallback_version(
+            installed_version.into_iter(),
+            &version,
+            pinned_version.is_some(),
+        ) else {
+            return Err(format!(
+                "{} {version} does not work ({error}) and there is no earlier version to fall \
+                 back to",
+                self.name
+            ));
+        };
+        if !is_broken {
+            zed::npm_install_package(self.name, &previous)?;
+        }
+        report_fallback(
+            self.name,
+            Some(language_server_id),
+            &version,
+            &error,
+            &previous,
+        );
+        Ok(())
+    }
+
+    /// Records the version of the package that failed its smoke test, next to
+    /// `node_modules`.
+    fn broken_marker(&self) -> String {
+        format!("{}{BROKEN_MARKER}", self.name)
+    }
+
+    /// Returns whether the given version failed its smoke test.
+    fn is_broken(&self, version: &str) -> bool {
+        fs::read_to_string(self.broken_marker())
+            .is_ok_and(|broken| version::same(broken.trim(), version))
+    }
+}
+
+/// Checks that a downloaded binary is an executable (ELF, Mach-O or PE).
+/// Downloaded binaries live at a path that changes with every version, so the
+/// extension isn't allowed to run them.
+pub(crate) fn check_executable(path: &str) -> Result<()> {
+    check_file(path, "an executable", |contents| {
+        [
+            b"\x7fELF".as_slice(),
+            b"\xfe\xed\xfa\xce",
+            b"\xfe\xed\xfa\xcf",
+            b"\xce\xfa\xed\xfe",
+            b"\xcf\xfa\xed\xfe",
+            b"\xca\xfe\xba\xbe",
+            b"MZ",
+        ]
+        .iter()
+        .any(|magic| contents.starts_with(magic))
+    })
+}
+
+/// Checks that a downloaded PHP archive is complete, which it is if it
+/// contains the stub that ends every phar.
+pub(crate) fn check_phar(path: &str) -> Result<()> {
+    check_file(path, "a PHP archive", |contents| {
+        contents
+            .windows(b"__HALT_COMPILER();".len())
+            .any(|window| window == b"__HALT_COMPILER();")
+    })
+}
+
+/// Checks that a script that Zed's Node.js runs is a non-empty text file.
+pub(crate) fn check_script(path: &str) -> Result<()> {
+    check_file(path, "a script", |contents| {
+        !contents.is_empty() && std::str::from_utf8(contents).is_ok()
+    })
+}
+
+fn check_file(path: &str, kind: &str, is_valid: impl FnOnce(&[u8]) -> bool) -> Result<()> {
+    let contents = fs::read(path).map_err(|error| format!("could not read {path}: {error}"))?;
+    if !is_valid(&contents) {
+        return Err(format!("{path} is not {kind}"));
+    }
+    Ok(())
+}
+
+/// Returns the version to keep as the last known-good one once `version`
+/// works: the newest of the `installed` versions that is older.
+fn last_known_good(installed: impl Iterator<Item = String>, version: &str) -> Option<String> {
+    installed
+        .filter(|installed| version::compare(installed, version).is_lt())
+        .max_by(|a, b| version::compare(a, b))
+}
+
+/// Returns the version to roll back to when `version` doesn't work: the newest
+/// of the other `installed` versions, or of the older ones if `version` is
+/// pinned, as a pinned version never silently turns into a newer one.
+fn fallback_version(
+    installed: impl Iterator<Item = String>,
+    version: &str,
+    is_pinned: bool,
+) -> Option<String> {
+    installed
+        .filter(|installed| {
+            !version::same(installed, version)
+                && (!is_pinned || version::compare(installed, version).is_lt())
+        })
+        .max_by(|a, b| version::compare(a, b))
+}
+
+/// Reports that the smoke test of a new version only checked its files, as the
+/// extension isn't allowed to run what it downloads.
+fn report_skipped_run(tool: &str, version: &str) {
+    eprintln!(
+        "{tool}: skipped running version {version} as its smoke test, which the extension \
+         isn't allowed to do; only checked its files"
+    );
+}
+
+/// Reports that a tool runs from an already installed version because checking
+/// for a newer one failed (usually because there is no network connection).
+pub(crate) fn report_offline_fallback(tool: &str, version: &str, error: &str) {
+    eprintln!(
+        "{tool}: could not check for updates ({error}), \
+         falling back to installed version {version}"
+    );
+}
+
+/// Reports that `tool` runs from the earlier version `previous` because
+/// `version` failed its smoke test, as the installation status of
+/// `language_server_id`, if any.
+fn report_fallback(
+    tool: &str,
+    language_server_id: Option<&LanguageServerId>,
+    version: &str,
+    error: &str,
+    previous: &str,
+) {
+    let message =
+        format!("{tool} {version} does not work ({error}), falling back to version {previous}");
+    eprintln!("{message}");
+    if let Some(language_server_id) = language_server_id {
+        zed::set_language_server_installation_status(
+            language_server_id,
+            &zed::LanguageServerInstallationStatus::Failed(message),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(versions: &[&str]) -> impl Iterator<Item = String> {
+        versions
+            .iter()
+            .map(|version| version.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn respells_pinned_tags() {
+        assert_eq!(other_tag_spelling("0.5.0"), "v0.5.0");
+        assert_eq!(other_tag_spelling("v0.5.0"), "0.5.0");
+        assert_eq!(other_tag_spelling("2025.07.25.0"), "v2025.07.25.0");
+    }
+
+    #[test]
+    fn names_release_assets_after_the_target() {
+        for (platform, arch, expected) in [
+            (
+                zed::Os::Linux,
+                zed::Architecture::X8664,
+                "tool-x86_64-unknown-linux-gnu.tar.gz",
+            ),
+            (
+                zed::Os::Mac,
+                zed::Architecture::Aarch64,
+                "tool-aarch64-apple-darwin.tar.gz",
+            ),
+            (
+                zed::Os::Windows,
+                zed::Architecture::X8664,
+                "tool-x86_64-pc-windows-msvc.zip",
+            ),
+        ] {
+            let (name, _) = asset_name("tool", platform, arch).unwrap();
+            assert_eq!(name, expected);
+        }
+        assert!(asset_name("tool", zed::Os::Linux, zed::Architecture::X86).is_err());
+    }
+
+    #[test]
+    fn keeps_the_newest_older_version() {
+        for (installed, version, expected) in [
+            (&["1.0.0", "1.2.0", "1.10.0"][..], "1.10.0", Some("1.2.0")),
+            (&["1.0.0", "2.0.0"], "1.5.0", Some("1.0.0")),
+            (&["2.0.0"], "2.0.0", None),
+            (&[], "1.0.0", None),
+        ] {
+            assert_eq!(
+                last_known_good(versions(installed), version).as_deref(),
+                expected,
+                "{installed:?} {version}"
+            );
+        }
+    }
+
+    #[test]
+    fn rolls_back_to_the_newest_other_version() {
+        for (installed, version, is_pinned, expected) in [
+            (&["1.0.0", "1.2.0"][..], "1.3.0", false, Some("1.2.0")),
+            (&["1.0.0", "2.0.0"], "1.5.0", false, Some("2.0.0")),
+            (&["1.0.0", "2.0.0"], "1.5.0", true, Some("1.0.0")),
+            (&["2.0.0"], "1.5.0", true, None),
+            (&["v1.5.0"], "1.5.0", false, None),
+            (&[], "1.0.0", false, None),
+        ] {
+            assert_eq!(
+                fallback_version(versions(installed), version, is_pinned).as_deref(),
+                expected,
+                "{installed:?} {version} pinned: {is_pinned}"
+            );
+        }
+    }
+
+    #[test]
+    fn checks_downloaded_files() {
+        let dir = env::temp_dir().join(format!("php-install-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, contents: &[u8]| {
+            let path = dir.join(name);
+            fs::write(&path, contents).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+
+        for (name, contents, check, is_valid) in [
+            (
+                "elf",
+                &b"\x7fELF\x02\x01"[..],
+                check_executable as fn(&str) -> _,
+                true,
+            ),
+            ("exe", b"MZ\x90\x00", check_executable, true),
+            ("html", b"<!DOCTYPE html>", check_executable, false),
+            (
+                "phar",
+                b"#!/usr/bin/env php\n<?php __HALT_COMPILER(); ?>",
+                check_phar,
+                true,
+            ),
+            (
+                "truncated.phar",
+                b"#!/usr/bin/env php\n<?php",
+                check_phar,
+                false,
+            ),
+            ("script.js", b"console.log(1);", check_script, true),
+            ("empty.js", b"", check_script, false),
+            ("binary.js", b"\xff\xfe", check_script, false),
+        ] {
+            let result = check(&file(name, contents));
+            assert_eq!(result.is_ok(), is_valid, "{name}");
+        }
+        assert!(check_script(&dir.join("missing.js").to_string_lossy()).is_err());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,3 +1,5 @@
+mod command;
+mod config;
 mod intelephense;
 mod phpactor;
 mod phpantom;
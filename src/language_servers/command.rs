@@ -0,0 +1,134 @@
+use zed_extension_api::settings::{CommandSettings, LspSettings};
+use zed_extension_api::{self as zed, Result};
+
+/// Builds a language server command, applying the user's
+/// `lsp.<server>.binary` settings on top of what the extension would run:
+///
+/// - `path` replaces the command. `arguments` then replace `standalone_args`,
+///   the arguments the server needs when it runs as a standalone binary.
+/// - Without `path`, `arguments` are appended to the extension's own arguments.
+/// - `env` is merged over the command's environment.
+///
+/// `default_command` is only called when no custom `path` is set, so a custom
+/// binary never triggers a download.
+pub(crate) fn command_with_binary_settings(
+    language_server_name: &str,
+    worktree: &zed::Worktree,
+    standalone_args: &[&str],
+    default_command: impl FnOnce() -> Result<zed::Command>,
+) -> Result<zed::Command> {
+    let binary = LspSettings::for_worktree(language_server_name, worktree)
+        .ok()
+        .and_then(|settings| settings.binary);
+    apply_binary_settings(binary, standalone_args, default_command)
+}
+
+fn apply_binary_settings(
+    binary: Option<CommandSettings>,
+    standalone_args: &[&str],
+    default_command: impl FnOnce() -> Result<zed::Command>,
+) -> Result<zed::Command> {
+    let (path, arguments, env) = match binary {
+        Some(binary) => (binary.path, binary.arguments, binary.env),
+        None => (None, None, None),
+    };
+
+    let mut command = match path {
+        Some(path) => zed::Command {
+            command: path,
+            args: arguments
+                .unwrap_or_else(|| standalone_args.iter().map(ToString::to_string).collect()),
+            env: Default::default(),
+        },
+        None => {
+            let mut command = default_command()?;
+            command.args.extend(arguments.unwrap_or_default());
+            command
+        }
+    };
+
+    let mut env = env.unwrap_or_default().into_iter().collect::<Vec<_>>();
+    env.sort();
+    for (key, value) in env {
+        match command
+            .env
+            .iter_mut()
+            .find(|(existing, _)| *existing == key)
+        {
+            Some((_, existing)) => *existing = value,
+            None => command.env.push((key, value)),
+        }
+    }
+
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn default_command() -> Result<zed::Command> {
+        Ok(zed::Command {
+            command: "node".to_string(),
+            args: vec!["server.js".to_string(), "--stdio".to_string()],
+            env: vec![("NODE_ENV".to_string(), "production".to_string())],
+        })
+    }
+
+    fn settings(path: Option<&str>, arguments: Option<&[&str]>) -> CommandSettings {
+        CommandSettings {
+            path: path.map(ToOwned::to_owned),
+            arguments: arguments.map(|arguments| arguments.iter().map(|a| a.to_string()).collect()),
+            env: Some(HashMap::from([
+                ("NODE_ENV".to_string(), "development".to_string()),
+                ("DEBUG".to_string(), "1".to_string()),
+            ])),
+        }
+    }
+
+    #[test]
+    fn applies_binary_settings() {
+        for (binary, command, args) in [
+            (None, "node", &["server.js", "--stdio"][..]),
+            (
+                Some(settings(None, Some(&["--verbose"]))),
+                "node",
+                &["server.js", "--stdio", "--verbose"],
+            ),
+            (
+                Some(settings(Some("/usr/bin/server"), None)),
+                "/usr/bin/server",
+                &["--stdio"],
+            ),
+            (
+                Some(settings(Some("/usr/bin/server"), Some(&["lsp"]))),
+                "/usr/bin/server",
+                &["lsp"],
+            ),
+        ] {
+            let has_env = binary.is_some();
+            let result = apply_binary_settings(binary, &["--stdio"], default_command).unwrap();
+            assert_eq!(result.command, command);
+            assert_eq!(result.args, args);
+            if has_env {
+                assert!(
+                    result
+                        .env
+                        .contains(&("NODE_ENV".to_string(), "development".to_string()))
+                );
+                assert!(result.env.contains(&("DEBUG".to_string(), "1".to_string())));
+            }
+        }
+    }
+
+    #[test]
+    fn never_runs_the_default_command_for_a_custom_path() {
+        let result =
+            apply_binary_settings(Some(settings(Some("/usr/bin/server"), None)), &[], || {
+                Err("would download the server".to_string())
+            });
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,103 @@
+use zed_extension_api::settings::LspSettings;
+use zed_extension_api::{self as zed, serde_json::Value};
+
+use crate::frameworks::FRAMEWORKS_SETTING;
+use crate::install::VERSION_SETTING;
+
+/// Keys in `lsp.<server>.settings` that configure the extension itself and are
+/// not forwarded to the language server.
+const EXTENSION_SETTINGS: &[&str] = &[VERSION_SETTING, FRAMEWORKS_SETTING];
+
+/// Merges `overrides` into `base`. Objects are merged key by key; any other
+/// value in `overrides` replaces the one in `base`.
+pub(crate) fn merge_json(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Returns the user's `lsp.<server>.initialization_options` merged over the
+/// `defaults` computed by the extension, or `None` if both are empty.
+pub(crate) fn initialization_options(
+    language_server_name: &str,
+    worktree: &zed::Worktree,
+    mut defaults: Value,
+) -> Option<Value> {
+    if let Some(options) = LspSettings::for_worktree(language_server_name, worktree)
+        .ok()
+        .and_then(|settings| settings.initialization_options)
+    {
+        merge_json(&mut defaults, options);
+    }
+
+    match &defaults {
+        Value::Null => None,
+        Value::Object(options) if options.is_empty() => None,
+        _ => Some(defaults),
+    }
+}
+
+/// Returns the user's `lsp.<server>.settings` merged over the `defaults`
+/// computed by the extension, without the keys that only configure the
+/// extension itself.
+pub(crate) fn workspace_settings(
+    language_server_name: &str,
+    worktree: &zed::Worktree,
+    mut defaults: Value,
+) -> Value {
+    if let Some(mut settings) = LspSettings::for_worktree(language_server_name, worktree)
+        .ok()
+        .and_then(|lsp_settings| lsp_settings.settings)
+    {
+        if let Some(settings) = settings.as_object_mut() {
+            for key in EXTENSION_SETTINGS {
+                settings.remove(*key);
+            }
+        }
+        merge_json(&mut defaults, settings);
+    }
+    defaults
+}
+
+#[cfg(test)]
+mod tests {
+    use zed_extension_api::serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn merges_overrides_key_by_key() {
+        for (base, overrides, expected) in [
+            (
+                json!({ "a": 1, "nested": { "b": 2, "c": 3 } }),
+                json!({ "nested": { "c": 4 }, "d": 5 }),
+                json!({ "a": 1, "nested": { "b": 2, "c": 4 }, "d": 5 }),
+            ),
+            (
+                json!({ "list": [1, 2] }),
+                json!({ "list": [3] }),
+                json!({ "list": [3] }),
+            ),
+            (json!(null), json!({ "a": 1 }), json!({ "a": 1 })),
+            (
+                json!({ "a": { "b": 1 } }),
+                json!({ "a": null }),
+                json!({ "a": null }),
+            ),
+        ] {
+            let mut merged = base.clone();
+            merge_json(&mut merged, overrides.clone());
+            assert_eq!(merged, expected, "{base} + {overrides}");
+        }
+    }
+}
@@ -1,11 +1,99 @@
 use std::{env, fs};
 
+use zed::process::Command;
 use zed::{CodeLabel, CodeLabelSpan};
-use zed_extension_api::settings::LspSettings;
-use zed_extension_api::{self as zed, LanguageServerId, Result, serde_json};
+use zed_extension_api::{
+    self as zed, LanguageServerId, Result,
+    serde_json::{self, Value},
+};
+
+use super::command::command_with_binary_settings;
+use super::config::{initialization_options, workspace_settings};
+use crate::composer::ComposerJson;
+use crate::frameworks::{DRUPAL_FILE_EXTENSIONS, Framework, LARAVEL_IDE_HELPER_FILES, frameworks};
+use crate::install::{NpmPackage, check_script, pinned_version};
 
 const SERVER_PATH: &str = "node_modules/intelephense/lib/intelephense.js";
-const PACKAGE_NAME: &str = "intelephense";
+const NPM_PACKAGE: NpmPackage = NpmPackage::new("intelephense");
+const LICENCE_KEY: &str = "licenceKey";
+
+/// The stubs Intelephense enables when `intelephense.stubs` is not set. Keep in
+/// sync with the default of `intelephense.stubs` in
+/// https://github.com/bmewburn/vscode-intelephense/blob/master/package.json.
+const DEFAULT_STUBS: &[&str] = &[
+    "apache",
+    "bcmath",
+    "bz2",
+    "calendar",
+    "com_dotnet",
+    "Core",
+    "ctype",
+    "curl",
+    "date",
+    "dba",
+    "dom",
+    "enchant",
+    "exif",
+    "FFI",
+    "fileinfo",
+    "filter",
+    "fpm",
+    "ftp",
+    "gd",
+    "gettext",
+    "gmp",
+    "hash",
+    "iconv",
+    "imap",
+    "intl",
+    "json",
+    "ldap",
+    "libxml",
+    "mbstring",
+    "meta",
+    "mysqli",
+    "oci8",
+    "odbc",
+    "openssl",
+    "pcntl",
+    "pcre",
+    "PDO",
+    "pdo_ibm",
+    "pdo_mysql",
+    "pdo_pgsql",
+    "pdo_sqlite",
+    "pgsql",
+    "Phar",
+    "posix",
+    "pspell",
+    "random",
+    "readline",
+    "Reflection",
+    "session",
+    "shmop",
+    "SimpleXML",
+    "snmp",
+    "soap",
+    "sockets",
+    "sodium",
+    "SPL",
+    "sqlite3",
+    "standard",
+    "superglobals",
+    "sysvmsg",
+    "sysvsem",
+    "sysvshm",
+    "tidy",
+    "tokenizer",
+    "xml",
+    "xmlreader",
+    "xmlrpc",
+    "xmlwriter",
+    "xsl",
+    "Zend OPcache",
+    "zip",
+    "zlib",
+];
 
 pub struct Intelephense {
     did_find_server: bool,
@@ -24,6 +112,16 @@ impl Intelephense {
         &mut self,
         language_server_id: &LanguageServerId,
         worktree: &zed::Worktree,
+    ) -> Result<zed::Command> {
+        command_with_binary_settings(Self::LANGUAGE_SERVER_ID, worktree, &["--stdio"], || {
+            self.default_command(language_server_id, worktree)
+        })
+    }
+
+    fn default_command(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
     ) -> Result<zed::Command> {
         if let Some(path) = worktree.which("intelephense") {
             return Ok(zed::Command {
@@ -33,7 +131,7 @@ impl Intelephense {
             });
         }
 
-        let server_path = self.server_script_path(language_server_id)?;
+        let server_path = self.server_script_path(language_server_id, worktree)?;
         Ok(zed::Command {
             command: zed::node_binary_path()?,
             args: vec![
@@ -52,60 +150,160 @@ impl Intelephense {
         fs::metadata(SERVER_PATH).is_ok_and(|stat| stat.is_file())
     }
 
-    fn server_script_path(&mut self, language_server_id: &LanguageServerId) -> Result<String> {
+    fn server_script_path(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<String> {
         let server_exists = self.server_exists();
         if self.did_find_server && server_exists {
             return Ok(SERVER_PATH.to_string());
         }
 
-        zed::set_language_server_installation_status(
+        let pinned_version = pinned_version(Self::LANGUAGE_SERVER_ID, worktree);
+        NPM_PACKAGE.install(
             language_server_id,
-            &zed::LanguageServerInstallationStatus::CheckingForUpdate,
-        );
-        let version = zed::npm_package_latest_version(PACKAGE_NAME)?;
+            SERVER_PATH,
+            pinned_version.as_deref(),
+            check_script,
+        )?;
 
-        if !server_exists
-            || zed::npm_package_installed_version(PACKAGE_NAME)?.as_ref() != Some(&version)
+        self.did_find_server = true;
+        Ok(SERVER_PATH.to_string())
+    }
+
+    pub fn language_server_initialization_options(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        let mut defaults = serde_json::Map::new();
+        if let Some(licence_key) = Self::settings_licence_key(worktree) {
+            defaults.insert(LICENCE_KEY.to_string(), licence_key.into());
+        }
+
+        let mut options =
+            initialization_options(Self::LANGUAGE_SERVER_ID, worktree, defaults.into());
+        if let Some(options) = options.as_mut().and_then(|options| options.as_object_mut())
+            && let Some(licence_key) = options.get(LICENCE_KEY).and_then(|key| key.as_str())
+            && !is_valid_licence_key(licence_key)
         {
             zed::set_language_server_installation_status(
                 language_server_id,
-                &zed::LanguageServerInstallationStatus::Downloading,
+                &zed::LanguageServerInstallationStatus::Failed(format!(
+                    "Ignoring malformed Intelephense licence key: expected 15 letters and digits, \
+                     found {} characters. Premium features stay disabled.",
+                    licence_key.chars().count()
+                )),
             );
-            let result = zed::npm_install_package(PACKAGE_NAME, &version);
-            match result {
-                Ok(()) => {
-                    if !self.server_exists() {
-                        Err(format!(
-                            "installed package '{PACKAGE_NAME}' did not contain expected path '{SERVER_PATH}'",
-                        ))?;
-                    }
-                }
-                Err(error) => {
-                    if !self.server_exists() {
-                        Err(error)?;
-                    }
-                }
-            }
+            options.remove(LICENCE_KEY);
         }
 
-        self.did_find_server = true;
-        Ok(SERVER_PATH.to_string())
+        Ok(options)
+    }
+
+    /// Looks up the licence key in `lsp.intelephense.settings.licenceKey`. A
+    /// `licenceKey` in the initialization options is merged over it.
+    fn settings_licence_key(worktree: &zed::Worktree) -> Option<String> {
+        let licence_key = workspace_settings(Self::LANGUAGE_SERVER_ID, worktree, Value::Null)
+            .get(LICENCE_KEY)?
+            .as_str()?
+            .trim()
+            .to_string();
+        (!licence_key.is_empty()).then_some(licence_key)
     }
 
     pub fn language_server_workspace_configuration(
         &mut self,
         worktree: &zed::Worktree,
     ) -> Result<Option<serde_json::Value>> {
-        let settings = LspSettings::for_worktree("intelephense", worktree)
-            .ok()
-            .and_then(|lsp_settings| lsp_settings.settings)
-            .unwrap_or_default();
+        let composer = ComposerJson::read(worktree);
+        let frameworks = frameworks(Self::LANGUAGE_SERVER_ID, worktree, composer.as_ref());
+
+        let mut additional_stubs = Self::additional_stubs(composer.as_ref(), worktree);
+        if frameworks.contains(&Framework::WordPress) {
+            additional_stubs.push("wordpress".to_string());
+        }
+        let mut defaults = serde_json::json!({});
+        // Setting `stubs` replaces Intelephense's defaults, so only set them
+        // when there is something to add.
+        if !additional_stubs.is_empty() {
+            defaults["stubs"] = DEFAULT_STUBS
+                .iter()
+                .map(ToString::to_string)
+                .chain(additional_stubs)
+                .collect::<Vec<_>>()
+                .into();
+        }
+        if let Some(php_version) = composer.as_ref().and_then(|c| c.php_version()) {
+            defaults["environment"]["phpVersion"] = php_version.into();
+        }
+        if frameworks.contains(&Framework::Drupal) {
+            defaults["files"]["associations"] = ["php", "phtml"]
+                .iter()
+                .chain(DRUPAL_FILE_EXTENSIONS)
+                .map(|extension| format!("*.{extension}"))
+                .collect::<Vec<_>>()
+                .into();
+        }
+        if frameworks.contains(&Framework::Laravel) {
+            let root_path = worktree.root_path();
+            let include_paths = LARAVEL_IDE_HELPER_FILES
+                .iter()
+                .filter(|file| worktree.read_text_file(file).is_ok())
+                .map(|file| format!("{root_path}/{file}"))
+                .collect::<Vec<_>>();
+            if !include_paths.is_empty() {
+                defaults["environment"]["includePaths"] = include_paths.into();
+            }
+        }
+        let settings = workspace_settings(Self::LANGUAGE_SERVER_ID, worktree, defaults);
 
         Ok(Some(serde_json::json!({
             "intelephense": settings
         })))
     }
 
+    /// Intelephense only knows about the extensions in its default `stubs`, so
+    /// returns the other ones the project requires through Composer and the
+    /// worktree's PHP has loaded.
+    fn additional_stubs(composer: Option<&ComposerJson>, worktree: &zed::Worktree) -> Vec<String> {
+        let mut extensions = composer
+            .map(ComposerJson::required_extensions)
+            .unwrap_or_default();
+        if worktree.which("php").is_some()
+            && let Ok(output) = Command::new("php")
+                .arg("-m")
+                .envs(worktree.shell_env())
+                .output()
+            && output.status == Some(0)
+        {
+            // Lists modules one per line, under `[PHP Modules]` and `[Zend Modules]`.
+            extensions.extend(
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('['))
+                    .map(ToOwned::to_owned),
+            );
+        }
+
+        let mut additional_stubs = extensions
+            .into_iter()
+            .map(|extension| {
+                // Stub names are case-sensitive (`PDO`, `SimpleXML`, ...).
+                DEFAULT_STUBS
+                    .iter()
+                    .find(|stub| stub.eq_ignore_ascii_case(&extension))
+                    .map_or_else(|| extension.to_lowercase(), ToString::to_string)
+            })
+            .filter(|stub| !DEFAULT_STUBS.contains(&stub.as_str()))
+            .collect::<Vec<_>>();
+        additional_stubs.sort();
+        additional_stubs.dedup();
+        additional_stubs
+    }
+
     pub fn label_for_completion(&self, completion: zed::lsp::Completion) -> Option<CodeLabel> {
         let label = &completion.label;
 
@@ -207,3 +405,8 @@ impl Intelephense {
         }
     }
 }
+
+/// Intelephense licence keys are 15 letters and digits.
+fn is_valid_licence_key(licence_key: &str) -> bool {
+    licence_key.len() == 15 && licence_key.chars().all(|c| c.is_ascii_alphanumeric())
+}
@@ -1,6 +1,15 @@
 use std::fs;
 
-use zed_extension_api::{self as zed, LanguageServerId, Result};
+use zed_extension_api::{self as zed, LanguageServerId, Result, serde_json};
+
+use super::command::command_with_binary_settings;
+use super::config::{initialization_options, workspace_settings};
+use crate::composer::ComposerJson;
+use crate::frameworks::{DRUPAL_FILE_EXTENSIONS, Framework, frameworks};
+use crate::install::{InstallDir, check_phar, pinned_version};
+
+const INSTALL_DIR: InstallDir = InstallDir::new("phpactor");
+const ASSET_NAME: &str = "phpactor.phar";
 
 pub struct Phpactor {
     cached_binary_path: Option<String>,
@@ -15,7 +24,116 @@ impl Phpactor {
         }
     }
 
-    pub fn language_server_binary_path(
+    pub fn language_server_command(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<zed::Command> {
+        command_with_binary_settings(
+            Self::LANGUAGE_SERVER_ID,
+            worktree,
+            &["language-server"],
+            || self.default_command(language_server_id, worktree),
+        )
+    }
+
+    fn default_command(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<zed::Command> {
+        let (platform, _) = zed::current_platform();
+
+        let phpactor_path = self.language_server_binary_path(language_server_id, worktree)?;
+
+        if platform == zed::Os::Windows {
+            // fix：.phar files are not executable https://github.com/zed-extensions/php/issues/23
+            let php_path = worktree
+                .which("php")
+                .ok_or("Could not find PHP in path! PHP needs to be installed for running phpactor on Windows")?;
+
+            let abs_phpactor_path = std::env::current_dir()
+                .map_err(|_| "Could not get current directory")?
+                .join(&phpactor_path);
+
+            if !fs::exists(&abs_phpactor_path).is_ok_and(|exists| exists) {
+                return Err(format!(
+                    "Could not resolve phpactor path {:?}!",
+                    phpactor_path
+                ));
+            };
+
+            Ok(zed::Command {
+                command: php_path,
+                args: vec![
+                    abs_phpactor_path.to_string_lossy().into(),
+                    "language-server".into(),
+                ],
+                env: Default::default(),
+            })
+        } else {
+            Ok(zed::Command {
+                command: phpactor_path,
+                args: vec!["language-server".into()],
+                env: Default::default(),
+            })
+        }
+    }
+
+    /// Phpactor reads its whole configuration from the initialization options.
+    /// By default, the static analysis and style integrations are enabled for
+    /// the tools the project installs through Composer, the PHP version comes
+    /// from `composer.json`, and framework presets enable Phpactor's Symfony
+    /// support and index Drupal's extra PHP file types.
+    pub fn language_server_initialization_options(
+        &mut self,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        let mut defaults = serde_json::Map::new();
+        for (tool, option) in [
+            ("phpstan", "language_server_phpstan.enabled"),
+            ("psalm", "language_server_psalm.enabled"),
+            ("phpcs", "php_code_sniffer.enabled"),
+        ] {
+            if worktree
+                .read_text_file(&format!("vendor/bin/{tool}"))
+                .is_ok()
+            {
+                defaults.insert(option.to_string(), true.into());
+            }
+        }
+        let composer = ComposerJson::read(worktree);
+        if let Some(php_version) = composer.as_ref().and_then(|c| c.php_version()) {
+            defaults.insert("php.version".to_string(), php_version.into());
+        }
+
+        let frameworks = frameworks(Self::LANGUAGE_SERVER_ID, worktree, composer.as_ref());
+        if frameworks.contains(&Framework::Symfony) {
+            defaults.insert("symfony.enabled".to_string(), true.into());
+        }
+        if frameworks.contains(&Framework::Drupal) {
+            let include_patterns = ["php", "phtml"]
+                .iter()
+                .chain(DRUPAL_FILE_EXTENSIONS)
+                .map(|extension| format!("/**/*.{extension}"))
+                .collect::<Vec<_>>();
+            defaults.insert(
+                "indexer.include_patterns".to_string(),
+                include_patterns.into(),
+            );
+        }
+
+        // Phpactor ignores workspace configuration, so `lsp.phpactor.settings`
+        // is sent here too, with `initialization_options` taking precedence.
+        let settings = workspace_settings(Self::LANGUAGE_SERVER_ID, worktree, defaults.into());
+        Ok(initialization_options(
+            Self::LANGUAGE_SERVER_ID,
+            worktree,
+            settings,
+        ))
+    }
+
+    fn language_server_binary_path(
         &mut self,
         language_server_id: &LanguageServerId,
         worktree: &zed::Worktree,
@@ -24,61 +142,27 @@ impl Phpactor {
             return Ok(path);
         }
 
-        if let Some(path) = &self.cached_binary_path
+        let pinned_version = pinned_version(Self::LANGUAGE_SERVER_ID, worktree);
+        if pinned_version.is_none()
+            && let Some(path) = &self.cached_binary_path
             && fs::metadata(path).is_ok_and(|stat| stat.is_file())
         {
             return Ok(path.clone());
         }
 
-        zed::set_language_server_installation_status(
-            language_server_id,
-            &zed::LanguageServerInstallationStatus::CheckingForUpdate,
-        );
-        let release = zed::latest_github_release(
+        let binary_path = INSTALL_DIR.ensure_release(
+            Some(language_server_id),
             "phpactor/phpactor",
-            zed::GithubReleaseOptions {
-                require_assets: true,
-                pre_release: false,
+            pinned_version.as_deref(),
+            ASSET_NAME,
+            || {
+                Ok((
+                    ASSET_NAME.to_string(),
+                    zed::DownloadedFileType::Uncompressed,
+                ))
             },
+            check_phar,
         )?;
-
-        let asset_name = "phpactor.phar";
-        let asset = release
-            .assets
-            .iter()
-            .find(|asset| asset.name == asset_name)
-            .ok_or_else(|| format!("no asset found matching {:?}", asset_name))?;
-
-        let version_dir = format!("phpactor-{}", release.version);
-        fs::create_dir_all(&version_dir).map_err(|e| format!("failed to create directory: {e}"))?;
-
-        let binary_path = format!("{version_dir}/phpactor.phar");
-
-        if !fs::metadata(&binary_path).is_ok_and(|stat| stat.is_file()) {
-            zed::set_language_server_installation_status(
-                language_server_id,
-                &zed::LanguageServerInstallationStatus::Downloading,
-            );
-
-            zed::download_file(
-                &asset.download_url,
-                &binary_path,
-                zed::DownloadedFileType::Uncompressed,
-            )
-            .map_err(|e| format!("failed to download file: {e}"))?;
-
-            zed::make_file_executable(&binary_path)?;
-
-            let entries =
-                fs::read_dir(".").map_err(|e| format!("failed to list working directory {e}"))?;
-            for entry in entries {
-                let entry = entry.map_err(|e| format!("failed to load directory entry {e}"))?;
-                if entry.file_name().to_str() != Some(&version_dir) {
-                    fs::remove_dir_all(entry.path()).ok();
-                }
-            }
-        }
-
         self.cached_binary_path = Some(binary_path.clone());
         Ok(binary_path)
     }
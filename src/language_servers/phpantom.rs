@@ -1,10 +1,14 @@
 use std::fs;
 
-use zed_extension_api::settings::LspSettings;
-use zed_extension_api::{self as zed, DownloadedFileType, LanguageServerId, Result};
+use zed_extension_api::{self as zed, LanguageServerId, Result, serde_json};
+
+use super::command::command_with_binary_settings;
+use super::config::{initialization_options, workspace_settings};
+use crate::install::{InstallDir, asset_name, check_executable, pinned_version};
 
 const REPO: &str = "PHPantom-dev/phpantom_lsp";
 const BINARY_NAME: &str = "phpantom_lsp";
+const INSTALL_DIR: InstallDir = InstallDir::new(BINARY_NAME);
 
 pub struct Phpantom {
     cached_binary_path: Option<String>,
@@ -24,27 +28,41 @@ impl Phpantom {
         language_server_id: &LanguageServerId,
         worktree: &zed::Worktree,
     ) -> Result<zed::Command> {
-        // Allow users to point at their own build via
-        // `lsp.phpantom.binary.{path,arguments}` in the settings.
-        if let Some(binary) = LspSettings::for_worktree("phpantom", worktree)
-            .ok()
-            .and_then(|settings| settings.binary)
-            && let Some(path) = binary.path
-        {
-            return Ok(zed::Command {
-                command: path,
-                args: binary.arguments.unwrap_or_default(),
+        command_with_binary_settings(Self::LANGUAGE_SERVER_ID, worktree, &[], || {
+            Ok(zed::Command {
+                command: self.language_server_binary_path(language_server_id, worktree)?,
+                args: vec![],
                 env: Default::default(),
-            });
-        }
-
-        Ok(zed::Command {
-            command: self.language_server_binary_path(language_server_id, worktree)?,
-            args: vec![],
-            env: Default::default(),
+            })
         })
     }
 
+    /// Unlike the other servers, PHPantom has no setting for the PHP version:
+    /// it reads the version constraint (and the autoloading rules) from the
+    /// project's `composer.json` itself, so the extension passes no defaults.
+    pub fn language_server_initialization_options(
+        &mut self,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        Ok(initialization_options(
+            Self::LANGUAGE_SERVER_ID,
+            worktree,
+            serde_json::json!({}),
+        ))
+    }
+
+    pub fn language_server_workspace_configuration(
+        &mut self,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        let settings =
+            workspace_settings(Self::LANGUAGE_SERVER_ID, worktree, serde_json::Value::Null);
+
+        Ok(Some(serde_json::json!({
+            "phpantom": settings
+        })))
+    }
+
     fn language_server_binary_path(
         &mut self,
         language_server_id: &LanguageServerId,
@@ -54,86 +72,27 @@ impl Phpantom {
             return Ok(path);
         }
 
-        if let Some(path) = &self.cached_binary_path
+        let pinned_version = pinned_version(Self::LANGUAGE_SERVER_ID, worktree);
+        if pinned_version.is_none()
+            && let Some(path) = &self.cached_binary_path
             && fs::metadata(path).is_ok_and(|stat| stat.is_file())
         {
             return Ok(path.clone());
         }
 
-        zed::set_language_server_installation_status(
-            language_server_id,
-            &zed::LanguageServerInstallationStatus::CheckingForUpdate,
-        );
-        let release = zed::latest_github_release(
-            REPO,
-            zed::GithubReleaseOptions {
-                require_assets: true,
-                pre_release: false,
-            },
-        )?;
-
         let (platform, arch) = zed::current_platform();
-
-        let (os_str, file_type) = match platform {
-            zed::Os::Mac => ("apple-darwin", DownloadedFileType::GzipTar),
-            zed::Os::Linux => ("unknown-linux-gnu", DownloadedFileType::GzipTar),
-            zed::Os::Windows => ("pc-windows-msvc", DownloadedFileType::Zip),
-        };
-
-        let arch_str = match arch {
-            zed::Architecture::Aarch64 => "aarch64",
-            zed::Architecture::X8664 => "x86_64",
-            _ => return Err(format!("unsupported architecture: {arch:?}")),
-        };
-
-        let asset_name = format!(
-            "{BINARY_NAME}-{arch_str}-{os_str}.{extension}",
-            extension = match file_type {
-                DownloadedFileType::GzipTar => "tar.gz",
-                DownloadedFileType::Zip => "zip",
-                _ => "",
-            }
-        );
-        let asset = release
-            .assets
-            .iter()
-            .find(|asset| asset.name == asset_name)
-            .ok_or_else(|| {
-                format!(
-                    "no release asset found matching {asset_name:?} — you may need to build \
-                     {BINARY_NAME} from source for your platform"
-                )
-            })?;
-
-        let version_dir = format!("{BINARY_NAME}-{}", release.version);
-        fs::create_dir_all(&version_dir).map_err(|e| format!("failed to create directory: {e}"))?;
-
-        let binary_path = match platform {
-            zed::Os::Windows => format!("{version_dir}/{BINARY_NAME}.exe"),
-            _ => format!("{version_dir}/{BINARY_NAME}"),
+        let binary_file = match platform {
+            zed::Os::Windows => format!("{BINARY_NAME}.exe"),
+            _ => BINARY_NAME.to_string(),
         };
-
-        if !fs::metadata(&binary_path).is_ok_and(|stat| stat.is_file()) {
-            zed::set_language_server_installation_status(
-                language_server_id,
-                &zed::LanguageServerInstallationStatus::Downloading,
-            );
-
-            zed::download_file(&asset.download_url, &version_dir, file_type)
-                .map_err(|e| format!("failed to download file: {e}"))?;
-
-            zed::make_file_executable(&binary_path)?;
-
-            let entries =
-                fs::read_dir(".").map_err(|e| format!("failed to list working directory: {e}"))?;
-            for entry in entries {
-                let entry = entry.map_err(|e| format!("failed to load directory entry: {e}"))?;
-                if entry.file_name().to_str() != Some(&version_dir) {
-                    fs::remove_dir_all(entry.path()).ok();
-                }
-            }
-        }
-
+        let binary_path = INSTALL_DIR.ensure_release(
+            Some(language_server_id),
+            REPO,
+            pinned_version.as_deref(),
+            &binary_file,
+            || asset_name(BINARY_NAME, platform, arch),
+            check_executable,
+        )?;
         self.cached_binary_path = Some(binary_path.clone());
         Ok(binary_path)
     }
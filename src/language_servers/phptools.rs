@@ -1,9 +1,13 @@
 use std::fs;
 use zed::{Architecture, Os};
-use zed_extension_api::settings::LspSettings;
 use zed_extension_api::{self as zed, LanguageServerId, Result, serde_json};
 
-const PACKAGE_NAME: &str = "devsense-php-ls";
+use super::command::command_with_binary_settings;
+use super::config::{initialization_options, workspace_settings};
+use crate::composer::ComposerJson;
+use crate::install::{NpmPackage, check_executable, pinned_version};
+
+const NPM_PACKAGE: NpmPackage = NpmPackage::new("devsense-php-ls");
 
 pub struct PhpTools {
     did_find_server: bool,
@@ -22,6 +26,16 @@ impl PhpTools {
         &mut self,
         language_server_id: &LanguageServerId,
         worktree: &zed::Worktree,
+    ) -> Result<zed::Command> {
+        command_with_binary_settings(Self::LANGUAGE_SERVER_ID, worktree, &["--stdio"], || {
+            self.default_command(language_server_id, worktree)
+        })
+    }
+
+    fn default_command(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
     ) -> Result<zed::Command> {
         if let Some(path) = worktree.which("phptools") {
             return Ok(zed::Command {
@@ -31,7 +45,7 @@ impl PhpTools {
             });
         }
 
-        let server_path = self.server_script_path(language_server_id)?;
+        let server_path = self.server_script_path(language_server_id, worktree)?;
         Ok(zed::Command {
             command: server_path,
             args: vec![
@@ -71,55 +85,49 @@ impl PhpTools {
         fs::metadata(self.server_file_path()).is_ok_and(|stat| stat.is_file())
     }
 
-    fn server_script_path(&mut self, language_server_id: &LanguageServerId) -> Result<String> {
+    fn server_script_path(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<String> {
         let server_exists = self.server_exists();
         let server_path = self.server_file_path();
         if self.did_find_server && server_exists {
             return Ok(server_path);
         }
 
-        zed::set_language_server_installation_status(
+        let pinned_version = pinned_version(Self::LANGUAGE_SERVER_ID, worktree);
+        NPM_PACKAGE.install(
             language_server_id,
-            &zed::LanguageServerInstallationStatus::CheckingForUpdate,
-        );
-        let version = zed::npm_package_latest_version(PACKAGE_NAME)?;
-
-        if !server_exists
-            || zed::npm_package_installed_version(PACKAGE_NAME)?.as_ref() != Some(&version)
-        {
-            zed::set_language_server_installation_status(
-                language_server_id,
-                &zed::LanguageServerInstallationStatus::Downloading,
-            );
-            let result = zed::npm_install_package(PACKAGE_NAME, &version);
-            match result {
-                Ok(()) => {
-                    if !self.server_exists() {
-                        Err(format!(
-                            "installed package '{PACKAGE_NAME}' did not contain expected path '{server_path}'",
-                        ))?;
-                    }
-                }
-                Err(error) => {
-                    if !self.server_exists() {
-                        Err(error)?;
-                    }
-                }
-            }
-        }
+            &server_path,
+            pinned_version.as_deref(),
+            check_executable,
+        )?;
 
         self.did_find_server = true;
         Ok(server_path)
     }
 
+    pub fn language_server_initialization_options(
+        &mut self,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        Ok(initialization_options(
+            Self::LANGUAGE_SERVER_ID,
+            worktree,
+            serde_json::json!({}),
+        ))
+    }
+
     pub fn language_server_workspace_configuration(
         &mut self,
         worktree: &zed::Worktree,
     ) -> Result<Option<serde_json::Value>> {
-        let settings = LspSettings::for_worktree("phptools", worktree)
-            .ok()
-            .and_then(|lsp_settings| lsp_settings.settings)
-            .unwrap_or_default();
+        let mut defaults = serde_json::json!({});
+        if let Some(php_version) = ComposerJson::read(worktree).and_then(|c| c.php_version()) {
+            defaults["php"]["version"] = php_version.into();
+        }
+        let settings = workspace_settings(Self::LANGUAGE_SERVER_ID, worktree, defaults);
 
         Ok(Some(serde_json::json!({
             "phptools": settings
@@ -0,0 +1,375 @@
+//! Maps the command line of a launch request (e.g. `php -dmemory_limit=-1
+//! vendor/bin/phpunit --filter Foo` from "debug this file" or a quick launch)
+//! onto the launch configuration of the debug adapters.
+
+use zed_extension_api::{
+    BuildTaskDefinition, BuildTaskDefinitionTemplatePayload, BuildTaskTemplate, LaunchRequest,
+    serde_json::{Map, Value, json},
+};
+
+/// PHP CLI options whose value is a separate argument when not attached, as in
+/// `-d memory_limit=-1`.
+const OPTIONS_WITH_VALUE: &[&str] = &["-c", "-d", "-z", "-t"];
+
+/// A launch command whose program is the PHP binary rather than the script.
+pub(crate) struct PhpCommand {
+    pub(crate) runtime_executable: String,
+    pub(crate) runtime_args: Vec<String>,
+    pub(crate) script: String,
+    pub(crate) args: Vec<String>,
+}
+
+impl PhpCommand {
+    /// Splits `program args…` into the PHP binary with its own options and the
+    /// script with its arguments. Returns `None` if `program` isn't a PHP
+    /// binary.
+    pub(crate) fn parse(program: &str, args: &[String]) -> Result<Option<Self>, String> {
+        if !is_php_binary(program) {
+            return Ok(None);
+        }
+
+        let mut runtime_args = Vec::new();
+        let mut args = args.iter();
+        let script = loop {
+            let Some(arg) = args.next() else {
+                break None;
+            };
+            match arg.as_str() {
+                "--" | "-f" => break args.next().cloned(),
+                "-r" | "-S" | "-B" | "-R" | "-F" | "-E" => {
+                    return Err(format!(
+                        "`{program} {arg}` has no script to debug. To debug requests to a web \
+                         server, attach to it instead"
+                    ));
+                }
+                option if OPTIONS_WITH_VALUE.contains(&option) => {
+                    runtime_args.push(option.to_string());
+                    runtime_args.extend(args.next().cloned());
+                }
+                option if option.starts_with('-') => runtime_args.push(option.to_string()),
+                script => break Some(script.to_string()),
+            }
+        }
+        .ok_or_else(|| {
+            format!(
+                "`{program}` is launched without a script to debug. To debug requests to a web \
+                 server, attach to it instead"
+            )
+        })?;
+
+        Ok(Some(Self {
+            runtime_executable: program.to_string(),
+            runtime_args,
+            script,
+            args: args.cloned().collect(),
+        }))
+    }
+}
+
+/// Builds the launch configuration of a launch request. "Debug this file" and
+/// quick launches run e.g. `php script.php`, but the adapters want the script
+/// as the `program` and the PHP options as `runtimeArgs`, so a PHP command is
+/// split up and returned as well.
+pub(crate) fn launch_configuration(
+    launch_config: &LaunchRequest,
+    stop_on_entry: bool,
+) -> Result<(Value, Option<PhpCommand>), String> {
+    let mut configuration = json!({
+        "request": "launch",
+        "program": launch_config.program,
+        "cwd": launch_config.cwd.as_deref().unwrap_or("$ZED_WORKTREE_ROOT"),
+        "args": launch_config.args,
+        "env": Value::Object(
+            launch_config.envs
+                .iter()
+                .map(|(k, v)| (k.clone(), v.to_owned().into()))
+                .collect::<Map<String, Value>>(),
+        ),
+        "stopOnEntry": stop_on_entry,
+    });
+
+    let command = PhpCommand::parse(&launch_config.program, &launch_config.args)?;
+    if let Some(command) = &command {
+        configuration["program"] = command.script.clone().into();
+        configuration["args"] = command.args.clone().into();
+        configuration["runtimeArgs"] = command.runtime_args.clone().into();
+    }
+    Ok((configuration, command))
+}
+
+/// Returns whether `program` names a PHP binary such as `php`, `php8.3`,
+/// `/usr/bin/php83`, `php-cli` or `php.exe`, as opposed to a script or a tool
+/// like `phpunit`.
+fn is_php_binary(program: &str) -> bool {
+    let file_name = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    let file_name = file_name.to_ascii_lowercase();
+    let name = file_name.strip_suffix(".exe").unwrap_or(&file_name);
+    let Some(version) = name.strip_prefix("php") else {
+        return false;
+    };
+    let version = version.strip_prefix('-').unwrap_or(version);
+    version == "cli" || version.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Returns the value the PHP options give to the ini `setting`, through
+/// `-dsetting=…` or `-d setting=…`. Like PHP, the last one wins.
+pub(crate) fn runtime_setting<'a>(runtime_args: &'a [String], setting: &str) -> Option<&'a str> {
+    let mut value = None;
+    let mut runtime_args = runtime_args.iter();
+    while let Some(arg) = runtime_args.next() {
+        let assignment = match arg.strip_prefix("-d") {
+            Some("") => runtime_args.next().map_or("", String::as_str),
+            Some(assignment) => assignment,
+            None => continue,
+        };
+        if let Some((name, setting_value)) = assignment.split_once('=')
+            && name == setting
+        {
+            value = Some(setting_value);
+        }
+    }
+    value
+}
+
+/// Returns the Xdebug port requested through `-dxdebug.client_port=…` in the
+/// PHP options or `client_port=…` in `XDEBUG_CONFIG`.
+pub(crate) fn requested_port(runtime_args: &[String], env: &[(String, String)]) -> Option<u16> {
+    if let Some(port) =
+        runtime_setting(runtime_args, "xdebug.client_port").and_then(|port| port.parse().ok())
+    {
+        return Some(port);
+    }
+
+    let (_, config) = env.iter().find(|(key, _)| key == "XDEBUG_CONFIG")?;
+    config
+        .split_whitespace()
+        .find_map(|setting| setting.strip_prefix("client_port=")?.parse().ok())
+}
+
+/// Installs the project's dependencies with Composer unless the autoloader
+/// given as the first argument exists. The path is passed as an argument, so
+/// it is never parsed as code.
+const INSTALL_IF_MISSING: &str = "if (!is_file($argv[1])) { \
+    passthru('composer install --no-interaction', $status); exit($status); }";
+
+/// Scripts from `vendor/` (e.g. `vendor/bin/phpunit`) only exist once the
+/// project's dependencies are installed, so install them before launching if
+/// that `vendor/` has no Composer autoloader yet. The task checks for it when
+/// it runs, since only the worktree's root is known here.
+pub(crate) fn build_task(script: &str, cwd: &str) -> Option<BuildTaskDefinition> {
+    let mut length = 0;
+    let vendor_dir = script.split_inclusive(['/', '\\']).find_map(|component| {
+        length += component.len();
+        (component.strip_suffix(['/', '\\']) == Some("vendor")).then(|| &script[..length])
+    })?;
+
+    Some(BuildTaskDefinition::Template(
+        BuildTaskDefinitionTemplatePayload {
+            locator_name: None,
+            template: BuildTaskTemplate {
+                label: "composer install".into(),
+                command: "php".into(),
+                args: vec![
+                    "-r".into(),
+                    INSTALL_IF_MISSING.into(),
+                    "--".into(),
+                    format!("{vendor_dir}autoload.php"),
+                ],
+                env: vec![],
+                cwd: Some(cwd.into()),
+            },
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn splits_php_commands() {
+        for (program, args, expected) in [
+            (
+                "php",
+                &["foo.php"][..],
+                Some(("php", &[][..], "foo.php", &[][..])),
+            ),
+            (
+                "/usr/bin/php8.3",
+                &["-dmemory_limit=-1", "vendor/bin/phpunit", "--filter", "Foo"],
+                Some((
+                    "/usr/bin/php8.3",
+                    &["-dmemory_limit=-1"][..],
+                    "vendor/bin/phpunit",
+                    &["--filter", "Foo"][..],
+                )),
+            ),
+            (
+                "php.exe",
+                &["-d", "xdebug.mode=debug", "-n", "-f", "-script.php", "-v"],
+                Some((
+                    "php.exe",
+                    &["-d", "xdebug.mode=debug", "-n"][..],
+                    "-script.php",
+                    &["-v"][..],
+                )),
+            ),
+            (
+                "php-cli",
+                &["--", "app.php"],
+                Some(("php-cli", &[], "app.php", &[])),
+            ),
+            ("vendor/bin/phpunit", &["--filter", "Foo"], None),
+            ("phpunit", &[], None),
+        ] {
+            let command = PhpCommand::parse(program, &strings(args)).unwrap();
+            assert_eq!(
+                command.map(|command| (
+                    command.runtime_executable,
+                    command.runtime_args,
+                    command.script,
+                    command.args,
+                )),
+                expected.map(|(runtime_executable, runtime_args, script, args)| (
+                    runtime_executable.to_string(),
+                    strings(runtime_args),
+                    script.to_string(),
+                    strings(args),
+                )),
+                "{program} {args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_commands_without_a_script() {
+        for args in [
+            &[][..],
+            &["-r", "echo 1;"],
+            &["-S", "localhost:8000"],
+            &["-n"],
+        ] {
+            assert!(
+                PhpCommand::parse("php", &strings(args)).is_err(),
+                "{args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn runs_the_script_of_php_commands() {
+        let launch_config = LaunchRequest {
+            program: "php".into(),
+            cwd: Some("/app".into()),
+            args: strings(&["-dmemory_limit=-1", "foo.php", "bar"]),
+            envs: vec![("APP_ENV".into(), "test".into())],
+        };
+        let (configuration, command) = launch_configuration(&launch_config, true).unwrap();
+        assert_eq!(
+            configuration,
+            json!({
+                "request": "launch",
+                "program": "foo.php",
+                "cwd": "/app",
+                "args": ["bar"],
+                "runtimeArgs": ["-dmemory_limit=-1"],
+                "env": { "APP_ENV": "test" },
+                "stopOnEntry": true,
+            })
+        );
+        assert_eq!(command.unwrap().runtime_executable, "php");
+
+        let launch_config = LaunchRequest {
+            program: "bin/console".into(),
+            cwd: None,
+            args: strings(&["cache:clear"]),
+            envs: vec![],
+        };
+        let (configuration, command) = launch_configuration(&launch_config, false).unwrap();
+        assert_eq!(configuration["program"], "bin/console");
+        assert_eq!(configuration["cwd"], "$ZED_WORKTREE_ROOT");
+        assert!(command.is_none());
+    }
+
+    #[test]
+    fn installs_dependencies_for_vendor_scripts() {
+        for (script, autoloader) in [
+            ("vendor/bin/phpunit", Some("vendor/autoload.php")),
+            ("./vendor/bin/pest", Some("./vendor/autoload.php")),
+            ("/app/vendor/bin/phpunit", Some("/app/vendor/autoload.php")),
+            (
+                "tools\\vendor\\bin\\phpstan",
+                Some("tools\\vendor\\autoload.php"),
+            ),
+            ("bin/console", None),
+            ("src/vendors.php", None),
+            ("vendor", None),
+        ] {
+            let task = build_task(script, "/app").map(|task| match task {
+                BuildTaskDefinition::Template(payload) => payload.template,
+                BuildTaskDefinition::ByName(name) => panic!("unexpected task {name}"),
+            });
+            assert_eq!(
+                task.as_ref()
+                    .and_then(|task| task.args.last())
+                    .map(String::as_str),
+                autoloader,
+                "{script}"
+            );
+            if let Some(task) = task {
+                assert_eq!(task.command, "php");
+                assert_eq!(task.args[..3], ["-r", INSTALL_IF_MISSING, "--"]);
+                assert_eq!(task.cwd.as_deref(), Some("/app"));
+            }
+        }
+    }
+
+    #[test]
+    fn finds_runtime_settings() {
+        for (runtime_args, expected) in [
+            (&["-dxdebug.mode=debug"][..], Some("debug")),
+            (&["-d", "xdebug.mode=debug,develop"], Some("debug,develop")),
+            (&["-dxdebug.mode=off", "-dxdebug.mode=debug"], Some("debug")),
+            (&["-dxdebug.mode="], Some("")),
+            (&["-dxdebug.modes=debug"], None),
+            (&["-n", "xdebug.mode=debug"], None),
+            (&["-d"], None),
+        ] {
+            assert_eq!(
+                runtime_setting(&strings(runtime_args), "xdebug.mode"),
+                expected,
+                "{runtime_args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_the_requested_port() {
+        let config = |value: &str| vec![("XDEBUG_CONFIG".to_string(), value.to_string())];
+        for (runtime_args, env, expected) in [
+            (&["-dxdebug.client_port=9010"][..], vec![], Some(9010)),
+            (&["-d", "xdebug.client_port=9011"], vec![], Some(9011)),
+            (
+                &[],
+                config("client_host=localhost client_port=9012"),
+                Some(9012),
+            ),
+            (
+                &["-dxdebug.client_port=9013"],
+                config("client_port=9014"),
+                Some(9013),
+            ),
+            (&["-dxdebug.mode=debug"], vec![], None),
+        ] {
+            assert_eq!(
+                requested_port(&strings(runtime_args), &env),
+                expected,
+                "{runtime_args:?} {env:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Paths on the machine Zed runs on. The extension is compiled to WebAssembly,
+//! where `std::path` follows WASI rather than that machine: `Path::is_absolute`
+//! and `MAIN_SEPARATOR` always behave as on Unix, even on Windows.
+
+use zed_extension_api as zed;
+
+/// Returns whether `path` is an absolute Unix or Windows path.
+pub(crate) fn is_absolute(path: &str) -> bool {
+    path.starts_with(['/', '\\'])
+        || path
+            .as_bytes()
+            .get(1..3)
+            .is_some_and(|separator| separator == b":\\" || separator == b":/")
+}
+
+/// Returns the path separator of the platform Zed runs on.
+pub(crate) fn separator() -> char {
+    match zed::current_platform().0 {
+        zed::Os::Windows => '\\',
+        zed::Os::Mac | zed::Os::Linux => '/',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_absolute_paths() {
+        for (path, expected) in [
+            ("/var/www", true),
+            ("\\\\server\\share", true),
+            ("C:\\Users\\me", true),
+            ("c:/projects", true),
+            ("./src", false),
+            ("src/index.php", false),
+            (".env", false),
+            ("C:relative", false),
+            ("", false),
+        ] {
+            assert_eq!(is_absolute(path), expected, "{path:?}");
+        }
+    }
+}
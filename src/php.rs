@@ -1,15 +1,28 @@
+mod composer;
+mod debug_adapters;
+mod frameworks;
+mod install;
 mod language_servers;
+mod launch_command;
+mod paths;
+mod phpdbg;
+mod schema;
+mod test_locator;
+mod version;
 mod xdebug;
+mod yaml;
 
-use std::fs;
 use zed::CodeLabel;
 use zed_extension_api::{
-    self as zed, DebugConfig, DebugScenario, LanguageServerId, Result,
-    StartDebuggingRequestArgumentsRequest, serde_json,
+    self as zed, DebugConfig, DebugRequest, DebugScenario, LanguageServerId, Result,
+    StartDebuggingRequestArgumentsRequest, TaskTemplate, serde_json,
 };
 
 use crate::{
+    debug_adapters::DebugAdapter,
     language_servers::{Intelephense, PhpTools, Phpactor, Phpantom},
+    phpdbg::Phpdbg,
+    test_locator::PhpTestLocator,
     xdebug::XDebug,
 };
 
@@ -18,7 +31,30 @@ struct PhpExtension {
     intelephense: Option<Intelephense>,
     phpactor: Option<Phpactor>,
     phpantom: Option<Phpantom>,
-    xdebug: XDebug,
+    debug_adapters: Vec<Box<dyn DebugAdapter>>,
+}
+
+impl PhpExtension {
+    fn debug_adapter(
+        &mut self,
+        adapter_name: &str,
+        method: &str,
+    ) -> Result<&mut Box<dyn DebugAdapter>, String> {
+        let supported = self
+            .debug_adapters
+            .iter()
+            .map(|adapter| adapter.name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.debug_adapters
+            .iter_mut()
+            .find(|adapter| adapter.name() == adapter_name)
+            .ok_or_else(|| {
+                format!(
+                    "PHP extension does not support unknown adapter in `{method}`: {adapter_name} (supported: [{supported}])"
+                )
+            })
+    }
 }
 
 impl zed::Extension for PhpExtension {
@@ -28,7 +64,7 @@ impl zed::Extension for PhpExtension {
             intelephense: None,
             phpactor: None,
             phpantom: None,
-            xdebug: XDebug::new(),
+            debug_adapters: vec![Box::new(XDebug::new()), Box::new(Phpdbg::new())],
         }
     }
 
@@ -48,44 +84,7 @@ impl zed::Extension for PhpExtension {
             }
             Phpactor::LANGUAGE_SERVER_ID => {
                 let phpactor = self.phpactor.get_or_insert_with(Phpactor::new);
-
-                let (platform, _) = zed::current_platform();
-
-                let phpactor_path =
-                    phpactor.language_server_binary_path(language_server_id, worktree)?;
-
-                if platform == zed::Os::Windows {
-                    // fix：.phar files are not executable https://github.com/zed-extensions/php/issues/23
-                    let php_path = worktree
-                        .which("php")
-                        .ok_or("Could not find PHP in path! PHP needs to be installed for running phpactor on Windows")?;
-
-                    let abs_phpactor_path = std::env::current_dir()
-                        .map_err(|_| "Could not get current directory")?
-                        .join(&phpactor_path);
-
-                    if !fs::exists(&abs_phpactor_path).is_ok_and(|exists| exists) {
-                        return Err(format!(
-                            "Could not resolve phpactor path {:?}!",
-                            phpactor_path
-                        ));
-                    };
-
-                    Ok(zed::Command {
-                        command: php_path,
-                        args: vec![
-                            abs_phpactor_path.to_string_lossy().into(),
-                            "language-server".into(),
-                        ],
-                        env: Default::default(),
-                    })
-                } else {
-                    Ok(zed::Command {
-                        command: phpactor_path,
-                        args: vec!["language-server".into()],
-                        env: Default::default(),
-                    })
-                }
+                phpactor.language_server_command(language_server_id, worktree)
             }
             Phpantom::LANGUAGE_SERVER_ID => {
                 let phpantom = self.phpantom.get_or_insert_with(Phpantom::new);
@@ -95,6 +94,32 @@ impl zed::Extension for PhpExtension {
         }
     }
 
+    fn language_server_initialization_options(
+        &mut self,
+        language_server_id: &LanguageServerId,
+        worktree: &zed::Worktree,
+    ) -> Result<Option<serde_json::Value>> {
+        match language_server_id.as_ref() {
+            PhpTools::LANGUAGE_SERVER_ID => self
+                .phptools
+                .get_or_insert_with(PhpTools::new)
+                .language_server_initialization_options(worktree),
+            Intelephense::LANGUAGE_SERVER_ID => self
+                .intelephense
+                .get_or_insert_with(Intelephense::new)
+                .language_server_initialization_options(language_server_id, worktree),
+            Phpactor::LANGUAGE_SERVER_ID => self
+                .phpactor
+                .get_or_insert_with(Phpactor::new)
+                .language_server_initialization_options(worktree),
+            Phpantom::LANGUAGE_SERVER_ID => self
+                .phpantom
+                .get_or_insert_with(Phpantom::new)
+                .language_server_initialization_options(worktree),
+            _ => Ok(None),
+        }
+    }
+
     fn language_server_workspace_configuration(
         &mut self,
         language_server_id: &LanguageServerId,
@@ -110,6 +135,11 @@ impl zed::Extension for PhpExtension {
         {
             return intelephense.language_server_workspace_configuration(worktree);
         }
+        if language_server_id.as_ref() == Phpantom::LANGUAGE_SERVER_ID
+            && let Some(phpantom) = self.phpantom.as_mut()
+        {
+            return phpantom.language_server_workspace_configuration(worktree);
+        }
 
         Ok(None)
     }
@@ -131,23 +161,38 @@ impl zed::Extension for PhpExtension {
         adapter_name: String,
         config: serde_json::Value,
     ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
-        if adapter_name != XDebug::NAME {
-            return Err(format!(
-                "PHP extension does not support unknown adapter in `dap_request_kind`: {adapter_name} (supported: [{}])",
-                XDebug::NAME
-            ));
-        }
-        self.xdebug.dap_request_kind(&config)
+        self.debug_adapter(&adapter_name, "dap_request_kind")?
+            .dap_request_kind(&config)
     }
     fn dap_config_to_scenario(&mut self, config: DebugConfig) -> Result<DebugScenario, String> {
-        if config.adapter != XDebug::NAME {
+        let adapter_name = config.adapter.clone();
+        self.debug_adapter(&adapter_name, "dap_config_to_scenario")?
+            .dap_config_to_scenario(config)
+    }
+    fn dap_locator_create_scenario(
+        &mut self,
+        locator_name: String,
+        build_task: TaskTemplate,
+        resolved_label: String,
+        debug_adapter_name: String,
+    ) -> Option<DebugScenario> {
+        if locator_name != PhpTestLocator::NAME {
+            return None;
+        }
+        PhpTestLocator.create_scenario(build_task, resolved_label, debug_adapter_name)
+    }
+    fn run_dap_locator(
+        &mut self,
+        locator_name: String,
+        build_task: TaskTemplate,
+    ) -> Result<DebugRequest, String> {
+        if locator_name != PhpTestLocator::NAME {
             return Err(format!(
-                "PHP extension does not support unknown adapter in `dap_config_to_scenario`: {} (supported: [{}])",
-                config.adapter,
-                XDebug::NAME
+                "PHP extension does not support unknown locator in `run_dap_locator`: {locator_name} (supported: [{}])",
+                PhpTestLocator::NAME
             ));
         }
-        self.xdebug.dap_config_to_scenario(config)
+        PhpTestLocator.run(build_task)
     }
     fn get_dap_binary(
         &mut self,
@@ -156,14 +201,7 @@ impl zed::Extension for PhpExtension {
         user_provided_debug_adapter_path: Option<String>,
         worktree: &zed_extension_api::Worktree,
     ) -> Result<zed_extension_api::DebugAdapterBinary, String> {
-        if config.adapter != XDebug::NAME {
-            return Err(format!(
-                "PHP extension does not support unknown adapter in `get_dap_binary`: {} (supported: [{}])",
-                adapter_name,
-                XDebug::NAME
-            ));
-        }
-        self.xdebug
+        self.debug_adapter(&adapter_name, "get_dap_binary")?
             .get_binary(config, user_provided_debug_adapter_path, worktree)
     }
 }
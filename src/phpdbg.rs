@@ -0,0 +1,124 @@
+use std::str::FromStr;
+
+use zed_extension_api::{
+    DebugAdapterBinary, DebugConfig, DebugRequest, DebugScenario, DebugTaskDefinition,
+    StartDebuggingRequestArguments, StartDebuggingRequestArgumentsRequest, TcpArguments,
+    TcpArgumentsTemplate, Worktree, resolve_tcp_template, serde_json::Value,
+};
+
+use crate::debug_adapters::{DebugAdapter, NativeAdapter};
+use crate::launch_command;
+use crate::schema;
+
+/// Debugs scripts with phpdbg, the debugger SAPI bundled with PHP, for setups
+/// without Xdebug. php-dbgp-adapter drives phpdbg, so there's nothing to attach
+/// to: phpdbg always runs the script itself.
+pub(super) struct Phpdbg {
+    native: NativeAdapter,
+}
+
+impl Phpdbg {
+    const NAME: &'static str = "phpdbg";
+    const SCHEMA: &'static str = include_str!("../debug_adapter_schemas/phpdbg.json");
+    const ATTACH_UNSUPPORTED: &'static str =
+        "phpdbg can only launch scripts; use the Xdebug adapter to attach to a web server";
+
+    pub(super) fn new() -> Self {
+        Self {
+            native: NativeAdapter::new(),
+        }
+    }
+}
+
+impl DebugAdapter for Phpdbg {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn dap_request_kind(
+        &self,
+        config: &Value,
+    ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
+        match config.get("request").and_then(Value::as_str) {
+            Some("launch") => Ok(StartDebuggingRequestArgumentsRequest::Launch),
+            Some("attach") => Err(Self::ATTACH_UNSUPPORTED.into()),
+            _ => Err("Invalid config".into()),
+        }
+    }
+
+    fn dap_config_to_scenario(&self, config: DebugConfig) -> Result<DebugScenario, String> {
+        let DebugRequest::Launch(launch_config) = &config.request else {
+            return Err(Self::ATTACH_UNSUPPORTED.into());
+        };
+        // phpdbg runs the script itself, so the PHP binary of a command such
+        // as `php script.php` is dropped and only its options are kept.
+        let (obj, _) = launch_command::launch_configuration(
+            launch_config,
+            config.stop_on_entry.unwrap_or_default(),
+        )?;
+
+        Ok(DebugScenario {
+            adapter: config.adapter,
+            label: config.label,
+            build: None,
+            config: obj.to_string(),
+            tcp_connection: None,
+        })
+    }
+
+    fn get_binary(
+        &mut self,
+        config: DebugTaskDefinition,
+        user_provided_debug_adapter_path: Option<String>,
+        worktree: &Worktree,
+    ) -> Result<DebugAdapterBinary, String> {
+        let mut configuration = Value::from_str(&config.config)
+            .map_err(|e| format!("Invalid JSON configuration: {e}"))?;
+        schema::validate(Self::SCHEMA, &configuration)?;
+        let request = self.dap_request_kind(&configuration)?;
+        if let Some(obj) = configuration.as_object_mut() {
+            obj.entry("cwd")
+                .or_insert_with(|| worktree.root_path().into());
+            if !obj.contains_key("runtimeExecutable") {
+                let phpdbg = worktree.which("phpdbg").ok_or_else(|| {
+                    "phpdbg was not found on your PATH. Install it (it ships with PHP, or as \
+                     the php-phpdbg package on most Linux distributions) or set \
+                     `runtimeExecutable` to its path"
+                        .to_string()
+                })?;
+                obj.insert("runtimeExecutable".into(), phpdbg.into());
+            }
+        }
+
+        let command = match user_provided_debug_adapter_path {
+            Some(path) => path,
+            None => self.native.binary_path(worktree)?,
+        };
+
+        let TcpArguments {
+            host,
+            port,
+            timeout,
+        } = resolve_tcp_template(config.tcp_connection.unwrap_or(TcpArgumentsTemplate {
+            host: None,
+            port: None,
+            timeout: None,
+        }))?;
+
+        Ok(DebugAdapterBinary {
+            command: Some(command),
+            arguments: vec!["--phpdbg".into(), format!("--server={port}")],
+            connection: Some(TcpArguments {
+                port,
+                host,
+                timeout,
+            }),
+            cwd: Some(worktree.root_path()),
+            envs: vec![],
+            request_args: StartDebuggingRequestArguments {
+                request,
+                configuration: configuration.to_string(),
+            },
+        })
+    }
+}
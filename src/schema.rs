@@ -0,0 +1,238 @@
+//! Validation of debug configurations against the adapter's JSON schema, so
+//! typos are reported by name instead of failing inside the Node adapter.
+//!
+//! Only the keywords the bundled schemas use are supported: `type`, `enum`,
+//! `const`, `properties`, `additionalProperties`, `items`, `oneOf`,
+//! `required` and `if`/`then`. Objects that list their `properties` reject
+//! unknown keys unless they allow `additionalProperties`, except in an `if`,
+//! which only tests the keys it lists.
+
+use zed_extension_api::serde_json::{self, Value};
+
+/// Validates `configuration` against `schema`, the source of a JSON schema.
+pub(crate) fn validate(schema: &str, configuration: &Value) -> Result<(), String> {
+    let schema = serde_json::from_str::<Value>(schema)
+        .map_err(|e| format!("Invalid debug adapter schema: {e}"))?;
+    check(&schema, configuration, "", true)
+        .map_err(|error| format!("Invalid configuration: {error}"))
+}
+
+fn check(schema: &Value, value: &Value, path: &str, reject_unknown: bool) -> Result<(), String> {
+    let name = if path.is_empty() {
+        "the configuration".to_string()
+    } else {
+        format!("`{path}`")
+    };
+
+    if let Some(expected) = schema.get("type").and_then(Value::as_str)
+        && !has_type(value, expected)
+    {
+        return Err(format!(
+            "{name} must be {}, found {}",
+            article(expected),
+            article(type_name(value))
+        ));
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        let allowed = allowed.iter().map(Value::to_string).collect::<Vec<_>>();
+        return Err(format!(
+            "{name} must be one of {}, found {value}",
+            allowed.join(", ")
+        ));
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        return Err(format!("{name} must be {expected}, found {value}"));
+    }
+    if let Some(alternatives) = schema.get("oneOf").and_then(Value::as_array) {
+        let mut errors = alternatives
+            .iter()
+            .map(|alternative| check(alternative, value, path, reject_unknown));
+        if !errors.any(|result| result.is_ok()) {
+            let expected = alternatives
+                .iter()
+                .filter_map(|alternative| alternative.get("type")?.as_str())
+                .map(article)
+                .collect::<Vec<_>>();
+            return Err(format!(
+                "{name} must be {}, found {}",
+                expected.join(" or "),
+                article(type_name(value))
+            ));
+        }
+    }
+
+    if let Some(items) = schema.get("items")
+        && let Some(values) = value.as_array()
+    {
+        for (index, item) in values.iter().enumerate() {
+            check(items, item, &format!("{path}[{index}]"), reject_unknown)?;
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    return Err(format!("{name} is missing the required property `{key}`"));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties");
+        for (key, item) in object {
+            let item_path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{path}.{key}")
+            };
+            match (
+                properties.and_then(|properties| properties.get(key)),
+                additional,
+            ) {
+                (Some(property), _) => check(property, item, &item_path, reject_unknown)?,
+                (None, Some(additional)) if additional.is_object() => {
+                    check(additional, item, &item_path, reject_unknown)?
+                }
+                (None, Some(Value::Bool(true))) => {}
+                (None, _) => {
+                    let Some(properties) = properties.filter(|_| reject_unknown) else {
+                        continue;
+                    };
+                    let mut error = format!("unknown property `{item_path}`");
+                    if let Some(suggestion) = closest(key, properties.keys()) {
+                        error.push_str(&format!(", did you mean `{suggestion}`?"));
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        if let Some(condition) = schema.get("if")
+            && check(condition, value, path, false).is_ok()
+            && let Some(then) = schema.get("then")
+        {
+            check(then, value, path, reject_unknown)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        expected => type_name(value) == expected,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn article(type_name: &str) -> String {
+    match type_name {
+        "null" => "null".to_string(),
+        "array" | "object" | "integer" => format!("an {type_name}"),
+        _ => format!("a {type_name}"),
+    }
+}
+
+/// Returns the property closest to a misspelled `key`, if any is close enough
+/// to be a plausible typo.
+fn closest<'a>(key: &str, properties: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    let key = key.to_lowercase();
+    properties
+        .map(|property| (edit_distance(&key, &property.to_lowercase()), property))
+        .filter(|(distance, _)| *distance <= (key.chars().count() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, property)| property.as_str())
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use zed_extension_api::serde_json::json;
+
+    use super::*;
+
+    const SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "request": { "enum": ["launch", "attach"] },
+            "program": { "type": "string" },
+            "port": { "type": "integer" }
+        },
+        "required": ["request"],
+        "if": { "properties": { "request": { "const": "launch" } } },
+        "then": { "required": ["program"] }
+    }"#;
+
+    #[test]
+    fn validates_configurations() {
+        for (configuration, expected) in [
+            (json!({ "request": "launch", "program": "a.php" }), Ok(())),
+            (json!({ "request": "attach", "port": 9003 }), Ok(())),
+            (
+                json!({ "request": "launch", "port": 9003 }),
+                Err("the configuration is missing the required property `program`"),
+            ),
+            (
+                json!({ "request": "attach", "port": "9003" }),
+                Err("`port` must be an integer, found a string"),
+            ),
+            (
+                json!({ "request": "attach", "prot": 9003 }),
+                Err("unknown property `prot`, did you mean `port`?"),
+            ),
+        ] {
+            assert_eq!(
+                validate(SCHEMA, &configuration),
+                expected.map_err(|error| format!("Invalid configuration: {error}")),
+                "{configuration}"
+            );
+        }
+    }
+    #[test]
+    fn accepts_any_xdebug_setting() {
+        let configuration = json!({
+            "request": "launch",
+            "program": "a.php",
+            "xdebugSettings": { "max_children": 128, "resolved_breakpoints": 1 },
+        });
+        assert_eq!(
+            validate(
+                include_str!("../debug_adapter_schemas/Xdebug.json"),
+                &configuration
+            ),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use zed_extension_api::{
+    DebugRequest, DebugScenario, LaunchRequest, TaskTemplate,
+    serde_json::{self, json},
+};
+
+use crate::xdebug::XDebug;
+
+/// Turns the PHPUnit and Pest tasks from `tasks.json` into Xdebug sessions, so
+/// tests can be debugged from the gutter.
+pub(super) struct PhpTestLocator;
+
+impl PhpTestLocator {
+    pub(super) const NAME: &'static str = "php-test";
+    const TEST_RUNNERS: &'static [&'static str] = &["phpunit", "pest"];
+
+    pub(super) fn create_scenario(
+        &self,
+        build_task: TaskTemplate,
+        resolved_label: String,
+        debug_adapter_name: String,
+    ) -> Option<DebugScenario> {
+        if debug_adapter_name != XDebug::NAME {
+            return None;
+        }
+        let request = Self::launch_request(build_task)?;
+
+        let mut env = request
+            .envs
+            .into_iter()
+            .map(|(key, value)| (key, value.into()))
+            .collect::<serde_json::Map<_, _>>();
+        env.insert("XDEBUG_MODE".into(), "debug".into());
+        env.insert("XDEBUG_TRIGGER".into(), "1".into());
+
+        let config = json!({
+            "request": "launch",
+            "program": request.program,
+            "args": request.args,
+            "cwd": request.cwd.unwrap_or_else(|| "$ZED_WORKTREE_ROOT".to_string()),
+            "env": env,
+        });
+
+        Some(DebugScenario {
+            adapter: debug_adapter_name,
+            label: resolved_label,
+            build: None,
+            config: config.to_string(),
+            tcp_connection: None,
+        })
+    }
+
+    pub(super) fn run(&self, build_task: TaskTemplate) -> Result<DebugRequest, String> {
+        Self::launch_request(build_task)
+            .map(DebugRequest::Launch)
+            .ok_or_else(|| "Task does not run PHPUnit or Pest".to_string())
+    }
+
+    /// Finds the test runner script in `./vendor/bin/phpunit …` or
+    /// `php vendor/bin/pest …` and keeps the arguments (filter and file) after it.
+    fn launch_request(build_task: TaskTemplate) -> Option<LaunchRequest> {
+        let mut command_line = std::iter::once(build_task.command).chain(build_task.args);
+        let program = command_line.find(|part| {
+            Path::new(part)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| Self::TEST_RUNNERS.contains(&name))
+        })?;
+        let program = match program.strip_prefix("./") {
+            Some(relative) => format!("$ZED_WORKTREE_ROOT/{relative}"),
+            None if program.starts_with("vendor/") => format!("$ZED_WORKTREE_ROOT/{program}"),
+            None => program,
+        };
+
+        // The tasks quote the filter for the shell, but the debug adapter starts
+        // PHP without one.
+        let args = command_line
+            .map(
+                |arg| match arg.strip_prefix('"').and_then(|a| a.strip_suffix('"')) {
+                    Some(unquoted) => unquoted.to_string(),
+                    None => arg,
+                },
+            )
+            .collect();
+
+        Some(LaunchRequest {
+            program,
+            cwd: build_task.cwd,
+            args,
+            envs: build_task.env,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(command: &str, args: &[&str]) -> TaskTemplate {
+        TaskTemplate {
+            label: "test".to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            env: vec![("APP_ENV".to_string(), "testing".to_string())],
+            cwd: Some("/app".to_string()),
+        }
+    }
+
+    #[test]
+    fn finds_the_test_runner() {
+        for (command, args, program, expected_args) in [
+            (
+                "./vendor/bin/phpunit",
+                &["--filter", "\"testItWorks\"", "tests/ExampleTest.php"][..],
+                "$ZED_WORKTREE_ROOT/vendor/bin/phpunit",
+                &["--filter", "testItWorks", "tests/ExampleTest.php"][..],
+            ),
+            (
+                "php",
+                &["vendor/bin/pest", "tests/Feature"],
+                "$ZED_WORKTREE_ROOT/vendor/bin/pest",
+                &["tests/Feature"],
+            ),
+            ("/usr/local/bin/phpunit", &[], "/usr/local/bin/phpunit", &[]),
+        ] {
+            let request = PhpTestLocator::launch_request(task(command, args)).unwrap();
+            assert_eq!(request.program, program, "{command} {args:?}");
+            assert_eq!(request.args, expected_args, "{command} {args:?}");
+            assert_eq!(request.cwd.as_deref(), Some("/app"));
+            assert_eq!(
+                request.envs,
+                [("APP_ENV".to_string(), "testing".to_string())]
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_other_tasks() {
+        for (command, args) in [
+            ("php", &["artisan", "serve"][..]),
+            ("composer", &["test"]),
+            ("./vendor/bin/phpunit-watcher", &[]),
+        ] {
+            assert!(
+                PhpTestLocator::launch_request(task(command, args)).is_none(),
+                "{command} {args:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Version numbers as the installed tools release them: `1.35.0`, `v0.5.0`,
+//! `2025.07.25.0` or `1.0.0-beta.2`.
+
+use std::cmp::Ordering;
+
+/// A version ordered by semver precedence. Releases may have any number of
+/// numeric components and missing ones count as zero, so `1.2` equals `1.2.0`.
+/// Build metadata (`+…`) is ignored.
+#[derive(Debug, Clone)]
+pub(crate) struct Version {
+    release: Vec<u64>,
+    pre_release: Vec<Identifier>,
+}
+
+/// A dot-separated pre-release identifier. Numeric identifiers have lower
+/// precedence than alphanumeric ones, which is the order of the variants.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Identifier {
+    Numeric(u64),
+    Alphanumeric(String),
+}
+
+impl Version {
+    /// Parses a version, with or without a leading `v`.
+    pub(crate) fn parse(version: &str) -> Option<Self> {
+        let version = version.trim();
+        let version = version
+            .strip_prefix(['v', 'V'])
+            .unwrap_or(version)
+            .split('+')
+            .next()?;
+        let (release, pre_release) = match version.split_once('-') {
+            Some((release, pre_release)) => (release, Some(pre_release)),
+            None => (version, None),
+        };
+
+        let release = release
+            .split('.')
+            .map(|component| component.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        let pre_release = pre_release
+            .into_iter()
+            .flat_map(|pre_release| pre_release.split('.'))
+            .map(|identifier| match identifier.parse() {
+                Ok(number) => Some(Identifier::Numeric(number)),
+                Err(_) if identifier.is_empty() => None,
+                Err(_) => Some(Identifier::Alphanumeric(identifier.to_string())),
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            release,
+            pre_release,
+        })
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let components = self.release.len().max(other.release.len());
+        let component = |release: &[u64], index: usize| release.get(index).copied().unwrap_or(0);
+        let release = (0..components)
+            .map(|index| component(&self.release, index).cmp(&component(&other.release, index)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal);
+        // A pre-release comes before the release itself.
+        let pre_release = match (self.pre_release.is_empty(), other.pre_release.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => self.pre_release.cmp(&other.pre_release),
+        };
+        release.then(pre_release)
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Version {}
+
+/// Orders two version strings by precedence. Versions that don't parse come
+/// before all others and are ordered as plain strings among themselves.
+pub(crate) fn compare(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => a.cmp(b),
+    }
+}
+
+/// Returns whether two version strings denote the same version, e.g. `v1.2.0`
+/// and `1.2.0`.
+pub(crate) fn same(a: &str, b: &str) -> bool {
+    compare(a, b).is_eq()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_versions_by_precedence() {
+        for (a, b, expected) in [
+            ("1.9.0", "1.10.0", Ordering::Less),
+            ("1.10.0", "1.9.0", Ordering::Greater),
+            ("v1.2.0", "1.2.0", Ordering::Equal),
+            ("1.2", "1.2.0", Ordering::Equal),
+            ("1.0.0+build.5", "1.0.0", Ordering::Equal),
+            ("2025.07.25.0", "2025.7.25.1", Ordering::Less),
+            ("1.0.0-beta.2", "1.0.0", Ordering::Less),
+            ("1.0.0-alpha", "1.0.0-beta", Ordering::Less),
+            ("1.0.0-beta.2", "1.0.0-beta.11", Ordering::Less),
+            ("1.0.0-1", "1.0.0-alpha", Ordering::Less),
+            ("1.0.0-rc.1", "0.9.9", Ordering::Greater),
+            ("latest", "0.1.0", Ordering::Less),
+            ("nightly", "latest", Ordering::Greater),
+        ] {
+            assert_eq!(compare(a, b), expected, "{a} vs {b}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_versions() {
+        for version in ["", "1..2", "1.x", "1.0.0-", "1.0.0-beta..1"] {
+            assert!(Version::parse(version).is_none(), "{version:?}");
+        }
+    }
+}
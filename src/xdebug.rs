@@ -1,78 +1,49 @@
-use std::{env, path::Path, str::FromStr, sync::OnceLock};
+mod docker_compose;
+mod env_file;
+mod preflight;
+mod presets;
+mod variables;
+mod vscode;
+
+use std::{env, net::Ipv4Addr, path::Path, str::FromStr};
 
 use zed_extension_api::{
-    DebugAdapterBinary, DebugConfig, DebugRequest, DebugScenario, DownloadedFileType,
-    GithubReleaseAsset, GithubReleaseOptions, StartDebuggingRequestArguments,
-    StartDebuggingRequestArgumentsRequest, TcpArguments, TcpArgumentsTemplate, download_file,
-    latest_github_release, node_binary_path, resolve_tcp_template,
+    DebugAdapterBinary, DebugConfig, DebugRequest, DebugScenario, DebugTaskDefinition,
+    DownloadedFileType, GithubReleaseAsset, StartDebuggingRequestArguments,
+    StartDebuggingRequestArgumentsRequest, TcpArguments, TcpArgumentsTemplate, Worktree,
+    download_file, node_binary_path, resolve_tcp_template,
     serde_json::{self, Value, json},
 };
 
+use crate::debug_adapters::{DebugAdapter, NativeAdapter};
+use crate::install::{InstallDir, check_script, github_release, report_offline_fallback};
+use crate::launch_command;
+use crate::schema;
+use crate::version;
+use presets::Preset;
+use variables::Variables;
+
 pub(super) struct XDebug {
-    current_version: OnceLock<String>,
+    current_version: Option<String>,
+    native: NativeAdapter,
 }
 
 impl XDebug {
     pub(super) const NAME: &'static str = "Xdebug";
     const ADAPTER_PATH: &'static str = "extension/out/phpDebug.js";
+    const INSTALL_DIR: InstallDir = InstallDir::new(Self::NAME);
+    const DEFAULT_PORT: u16 = 9003;
+    const SCHEMA: &'static str = include_str!("../debug_adapter_schemas/Xdebug.json");
     pub(super) fn new() -> Self {
         Self {
             current_version: Default::default(),
+            native: NativeAdapter::new(),
         }
     }
-    pub(super) fn dap_request_kind(
-        &self,
-        config: &serde_json::Value,
-    ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
-        config
-            .get("request")
-            .and_then(|v| {
-                v.as_str().and_then(|s| {
-                    s.eq("launch")
-                        .then_some(StartDebuggingRequestArgumentsRequest::Launch)
-                })
-            })
-            .ok_or_else(|| "Invalid config".into())
-    }
-
-    pub(crate) fn dap_config_to_scenario(
-        &self,
-        config: DebugConfig,
-    ) -> Result<DebugScenario, String> {
-        let obj = match &config.request {
-            DebugRequest::Attach(_) => {
-                return Err("Php adapter doesn't support attaching".into());
-            }
-            DebugRequest::Launch(launch_config) => json!({
-                "program": launch_config.program,
-                "cwd": launch_config.cwd,
-                "args": launch_config.args,
-                "env": serde_json::Value::Object(
-                    launch_config.envs
-                        .iter()
-                        .map(|(k, v)| (k.clone(), v.to_owned().into()))
-                        .collect::<serde_json::Map<String, serde_json::Value>>(),
-                ),
-                "stopOnEntry": config.stop_on_entry.unwrap_or_default(),
-            }),
-        };
-
-        Ok(DebugScenario {
-            adapter: config.adapter,
-            label: config.label,
-            build: None,
-            config: obj.to_string(),
-            tcp_connection: None,
-        })
-    }
-    fn fetch_latest_adapter_version() -> Result<(GithubReleaseAsset, String), String> {
-        let release = latest_github_release(
-            "xdebug/vscode-php-debug",
-            GithubReleaseOptions {
-                require_assets: true,
-                pre_release: false,
-            },
-        )?;
+    fn fetch_adapter_release(
+        pinned_tag: Option<&str>,
+    ) -> Result<(GithubReleaseAsset, String), String> {
+        let release = github_release("xdebug/vscode-php-debug", pinned_tag)?;
 
         let asset_name = format!("php-debug-{}.vsix", release.version.trim_start_matches("v"));
 
@@ -85,28 +56,123 @@ impl XDebug {
         Ok((asset, release.version))
     }
 
-    fn get_installed_binary(
-        &mut self,
-        task_definition: zed_extension_api::DebugTaskDefinition,
-        user_provided_debug_adapter_path: Option<String>,
+    /// Returns the release tag the configuration pins the adapter to through
+    /// `adapterVersion`, accepting both `1.35.0` and `v1.35.0`.
+    fn pinned_tag(configuration: &Value) -> Option<String> {
+        let version = configuration.get("adapterVersion")?.as_str()?;
+        Some(format!("v{}", version.trim_start_matches("v")))
+    }
+
+    /// Downloads the adapter and returns the version to use, which is an
+    /// earlier one if the download doesn't work.
+    fn install(
+        asset: &GithubReleaseAsset,
+        version: &str,
+        is_pinned: bool,
+    ) -> Result<String, String> {
+        let output_path = Self::INSTALL_DIR.create_version_dir(version)?;
+        download_file(&asset.download_url, &output_path, DownloadedFileType::Zip)?;
+        Self::verify_install(version, is_pinned)
+    }
+
+    fn verify_install(version: &str, is_pinned: bool) -> Result<String, String> {
+        Self::INSTALL_DIR.verify_install(None, version, is_pinned, Self::ADAPTER_PATH, check_script)
+    }
+
+    /// Containers reach the host through its network interfaces, never through
+    /// its loopback one, so listen on all of them unless told otherwise.
+    fn listen_on_all_interfaces(configuration: &mut serde_json::Map<String, Value>) {
+        let listens_on_loopback = configuration
+            .get("hostname")
+            .and_then(Value::as_str)
+            .is_none_or(|hostname| ["localhost", "127.0.0.1", "::1"].contains(&hostname));
+        if listens_on_loopback {
+            configuration.insert("hostname".into(), "0.0.0.0".into());
+        }
+    }
+
+    /// Makes a launched script connect to the adapter even if the user's
+    /// php.ini doesn't enable step debugging, unless the configuration already
+    /// sets up Xdebug itself.
+    fn inject_runtime_settings(
+        configuration: &mut serde_json::Map<String, Value>,
         worktree: &zed_extension_api::Worktree,
-    ) -> Result<zed_extension_api::DebugAdapterBinary, String> {
+    ) {
+        // With port 0 the adapter picks a free port and substitutes `${port}`.
+        let client_port = match configuration.get("port").and_then(Value::as_u64) {
+            Some(0) => "${port}".to_string(),
+            Some(port) => port.to_string(),
+            None => Self::DEFAULT_PORT.to_string(),
+        };
+
+        if !configuration.contains_key("runtimeExecutable")
+            && let Some(php) = worktree.which("php")
+        {
+            configuration.insert("runtimeExecutable".into(), php.into());
+        }
+
+        let runtime_args = configuration
+            .entry("runtimeArgs")
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Some(runtime_args) = runtime_args.as_array_mut() {
+            for (setting, value) in [
+                ("xdebug.mode", "debug"),
+                ("xdebug.start_with_request", "yes"),
+                ("xdebug.client_port", &client_port),
+            ] {
+                let prefix = format!("-d{setting}=");
+                let is_set = runtime_args
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .any(|arg| arg.starts_with(&prefix));
+                if !is_set {
+                    runtime_args.push(format!("{prefix}{value}").into());
+                }
+            }
+        }
+
+        let env = configuration
+            .entry("env")
+            .or_insert_with(|| Value::Object(Default::default()));
+        if let Some(env) = env.as_object_mut() {
+            env.entry("XDEBUG_MODE").or_insert_with(|| "debug".into());
+            env.entry("XDEBUG_CONFIG").or_insert_with(|| {
+                format!("client_port={client_port} start_with_request=yes").into()
+            });
+        }
+    }
+
+    /// Returns the command and arguments that run vscode-php-debug.
+    fn node_adapter_command(
+        &self,
+        user_provided_debug_adapter_path: Option<String>,
+    ) -> Result<(String, Vec<String>), String> {
         let adapter_path = if let Some(user_installed_path) = user_provided_debug_adapter_path {
             user_installed_path
         } else {
             let version = self
                 .current_version
-                .get()
-                .cloned()
+                .clone()
                 .ok_or_else(|| "no installed version of Xdebug found".to_string())?;
             env::current_dir()
                 .unwrap()
-                .join(Self::NAME)
-                .join(format!("{}_{version}", Self::NAME))
+                .join(Self::INSTALL_DIR.version_dir(&version))
                 .to_string_lossy()
                 .into_owned()
         };
+        let script = Path::new(&adapter_path)
+            .join(Self::ADAPTER_PATH)
+            .to_string_lossy()
+            .into_owned();
+        Ok((node_binary_path()?, vec![script]))
+    }
 
+    fn get_installed_binary(
+        &mut self,
+        task_definition: zed_extension_api::DebugTaskDefinition,
+        (command, mut arguments): (String, Vec<String>),
+        worktree: &zed_extension_api::Worktree,
+    ) -> Result<zed_extension_api::DebugAdapterBinary, String> {
         let tcp_connection = task_definition
             .tcp_connection
             .unwrap_or(TcpArgumentsTemplate {
@@ -122,20 +188,83 @@ impl XDebug {
 
         let mut configuration = Value::from_str(&task_definition.config)
             .map_err(|e| format!("Invalid JSON configuration: {e}"))?;
+        if let Some(obj) = configuration.as_object_mut() {
+            vscode::translate(obj, zed_extension_api::current_platform().0);
+        }
+        schema::validate(Self::SCHEMA, &configuration)?;
         if let Some(obj) = configuration.as_object_mut() {
             obj.entry("cwd")
                 .or_insert_with(|| worktree.root_path().into());
         }
 
+        let request = self.dap_request_kind(&configuration)?;
+
+        // With port 0 the adapter picks one itself and substitutes `${port}`.
+        let xdebug_port = match configuration.get("port").and_then(Value::as_u64) {
+            Some(0) => None,
+            Some(port) => Some(port),
+            None => Some(Self::DEFAULT_PORT.into()),
+        };
+        if let Some(obj) = configuration.as_object_mut() {
+            Variables::new(worktree, xdebug_port).substitute(obj)?;
+        }
+
+        if request == StartDebuggingRequestArgumentsRequest::Launch
+            && let Some(obj) = configuration.as_object_mut()
+            && obj.contains_key("program")
+        {
+            // The `envFile` goes first, so its Xdebug settings win over the
+            // injected ones.
+            env_file::apply(obj, worktree)?;
+            Self::inject_runtime_settings(obj, worktree);
+            preflight::check(obj, xdebug_port, worktree)?;
+        }
+        let is_native = configuration.get("backend").and_then(Value::as_str) == Some("native");
+        // vscode-php-debug has no attach request; a launch without a `program`
+        // listens for incoming Xdebug connections instead. php-dbgp-adapter
+        // handles attach itself.
+        let adapter_request = match request {
+            StartDebuggingRequestArgumentsRequest::Attach if !is_native => {
+                StartDebuggingRequestArgumentsRequest::Launch
+            }
+            request => request,
+        };
+        if request == StartDebuggingRequestArgumentsRequest::Attach
+            && let Some(obj) = configuration.as_object_mut()
+        {
+            let preset = match obj.remove("preset").as_ref().and_then(Value::as_str) {
+                None | Some("auto") => Preset::detect(worktree),
+                Some("none") => None,
+                Some(name) => Some(
+                    Preset::from_name(name)
+                        .ok_or_else(|| format!("Unknown Xdebug preset: {name:?}"))?,
+                ),
+            };
+            let has_path_mappings = obj
+                .get("pathMappings")
+                .and_then(Value::as_object)
+                .is_some_and(|mappings| !mappings.is_empty());
+
+            if let Some(preset) = preset {
+                preset.apply(obj, &worktree.root_path());
+                Self::listen_on_all_interfaces(obj);
+            } else if !has_path_mappings
+                && let Some(mappings) = docker_compose::path_mappings(worktree)
+            {
+                obj.insert("pathMappings".into(), mappings.into());
+                Self::listen_on_all_interfaces(obj);
+            }
+
+            if adapter_request == StartDebuggingRequestArgumentsRequest::Launch {
+                obj.insert("request".into(), "launch".into());
+            }
+            obj.remove("program");
+        }
+
+        arguments.push(format!("--server={}", port));
         Ok(DebugAdapterBinary {
-            command: Some(node_binary_path()?),
-            arguments: vec![
-                Path::new(&adapter_path)
-                    .join(Self::ADAPTER_PATH)
-                    .to_string_lossy()
-                    .into_owned(),
-                format!("--server={}", port),
-            ],
+            command: Some(command),
+            arguments,
             connection: Some(TcpArguments {
                 port,
                 host,
@@ -144,50 +273,142 @@ impl XDebug {
             cwd: Some(worktree.root_path()),
             envs: vec![],
             request_args: StartDebuggingRequestArguments {
-                request: self.dap_request_kind(&configuration)?,
+                request: adapter_request,
                 configuration: configuration.to_string(),
             },
         })
     }
-    pub(crate) fn get_binary(
+}
+
+impl DebugAdapter for XDebug {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn dap_request_kind(
+        &self,
+        config: &Value,
+    ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
+        match config.get("request").and_then(Value::as_str) {
+            Some("launch") => Ok(StartDebuggingRequestArgumentsRequest::Launch),
+            Some("attach") => Ok(StartDebuggingRequestArgumentsRequest::Attach),
+            _ => Err("Invalid config".into()),
+        }
+    }
+
+    fn dap_config_to_scenario(&self, config: DebugConfig) -> Result<DebugScenario, String> {
+        let (obj, build) = match &config.request {
+            // Xdebug connects to the debugger, not the other way around, so
+            // attaching means listening for connections from a web server or
+            // php-fpm instead of launching a script.
+            DebugRequest::Attach(_) => (
+                json!({
+                    "request": "attach",
+                    "preset": "auto",
+                    "hostname": "localhost",
+                    "port": Self::DEFAULT_PORT,
+                    "pathMappings": {},
+                    "stopOnEntry": config.stop_on_entry.unwrap_or_default(),
+                }),
+                None,
+            ),
+            DebugRequest::Launch(launch_config) => {
+                let (mut obj, command) = launch_command::launch_configuration(
+                    launch_config,
+                    config.stop_on_entry.unwrap_or_default(),
+                )?;
+                let mut runtime_args = Vec::new();
+                if let Some(command) = command {
+                    obj["runtimeExecutable"] = command.runtime_executable.into();
+                    runtime_args = command.runtime_args;
+                }
+                if let Some(port) =
+                    launch_command::requested_port(&runtime_args, &launch_config.envs)
+                {
+                    obj["port"] = port.into();
+                }
+
+                let build = obj["program"]
+                    .as_str()
+                    .zip(obj["cwd"].as_str())
+                    .and_then(|(script, cwd)| launch_command::build_task(script, cwd));
+                (obj, build)
+            }
+        };
+
+        Ok(DebugScenario {
+            adapter: config.adapter,
+            label: config.label,
+            build,
+            config: obj.to_string(),
+            // The adapter serves the editor on loopback. Zed picks a free port
+            // for it, which never collides with the port Xdebug connects to.
+            tcp_connection: Some(TcpArgumentsTemplate {
+                host: Some(Ipv4Addr::LOCALHOST.into()),
+                port: None,
+                timeout: None,
+            }),
+        })
+    }
+    fn get_binary(
         &mut self,
-        config: zed_extension_api::DebugTaskDefinition,
+        config: DebugTaskDefinition,
         user_provided_debug_adapter_path: Option<String>,
-        worktree: &zed_extension_api::Worktree,
-    ) -> Result<zed_extension_api::DebugAdapterBinary, String> {
-        if self.current_version.get_mut().is_none() {
-            if let Ok((asset, version)) = Self::fetch_latest_adapter_version() {
-                let output_path = format!("{0}/{0}_{1}", Self::NAME, version);
-                if !Path::new(&output_path).exists() {
-                    std::fs::remove_dir_all(Self::NAME).ok();
-                    std::fs::create_dir_all(Self::NAME)
-                        .map_err(|e| format!("Failed to create directory: {}", e))?;
-                    download_file(&asset.download_url, &output_path, DownloadedFileType::Zip)?;
+        worktree: &Worktree,
+    ) -> Result<DebugAdapterBinary, String> {
+        let configuration = Value::from_str(&config.config).ok();
+        if configuration
+            .as_ref()
+            .and_then(|configuration| configuration.get("backend")?.as_str())
+            == Some("native")
+        {
+            let path = match user_provided_debug_adapter_path {
+                Some(path) => path,
+                None => self.native.binary_path(worktree)?,
+            };
+            return self.get_installed_binary(config, (path, Vec::new()), worktree);
+        }
+
+        let pinned_tag = configuration.as_ref().and_then(Self::pinned_tag);
+        if let Some(tag) = &pinned_tag {
+            let is_current = self
+                .current_version
+                .as_deref()
+                .is_some_and(|current| version::same(current, tag));
+            if !is_current {
+                let version = match Self::INSTALL_DIR.installed_version(tag, Self::ADAPTER_PATH) {
+                    Some(version) => version,
+                    None => {
+                        let (asset, version) = Self::fetch_adapter_release(Some(tag))?;
+                        Self::install(&asset, &version, true)?
+                    }
+                };
+                self.current_version = Some(version);
+            }
+        } else if self.current_version.is_none() {
+            match Self::fetch_adapter_release(None) {
+                Ok((asset, version)) => {
+                    let version = if !Path::new(&Self::INSTALL_DIR.version_dir(&version)).exists() {
+                        Self::install(&asset, &version, false)?
+                    } else if Self::INSTALL_DIR.is_broken(&version) {
+                        Self::verify_install(&version, false)?
+                    } else {
+                        version
+                    };
+                    self.current_version = Some(version);
                 }
-                self.current_version.set(version).ok();
-            } else {
-                // Just find the highest version we currently have.
-                let prefix = format!("{}_", Self::NAME);
-                let mut version = std::fs::read_dir(Self::NAME)
-                    .ok()
-                    .into_iter()
-                    .flatten()
-                    .filter_map(|e| {
-                        e.ok().and_then(|entry| {
-                            entry
-                                .file_name()
-                                .to_string_lossy()
-                                .strip_prefix(&prefix)
-                                .map(ToOwned::to_owned)
-                        })
-                    })
-                    .max();
-
-                if let Some(version) = version.take() {
-                    self.current_version.set(version).ok();
+                Err(error) => {
+                    // Just find the highest version we currently have.
+                    if let Some(version) =
+                        Self::INSTALL_DIR.latest_installed_version(Self::ADAPTER_PATH)
+                    {
+                        report_offline_fallback(Self::NAME, &version, &error);
+                        self.current_version = Some(version);
+                    }
                 }
             }
         }
-        self.get_installed_binary(config, user_provided_debug_adapter_path, worktree)
+        let command = self.node_adapter_command(user_provided_debug_adapter_path)?;
+        self.get_installed_binary(config, command, worktree)
     }
 }
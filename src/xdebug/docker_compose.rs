@@ -0,0 +1,188 @@
+//! Path mappings for PHP that runs in Docker Compose services.
+
+use zed_extension_api::{
+    self as zed,
+    serde_json::{Map, Value},
+};
+
+use crate::yaml;
+
+const COMPOSE_FILES: &[&str] = &[
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+/// Returns `pathMappings` from container paths to local paths for every bind
+/// mount of the worktree (or a directory inside it) into a PHP service.
+pub(super) fn path_mappings(worktree: &zed::Worktree) -> Option<Map<String, Value>> {
+    let compose = COMPOSE_FILES
+        .iter()
+        .find_map(|file| worktree.read_text_file(file).ok())
+        .and_then(|contents| yaml::parse(&contents))?;
+    let root_path = worktree.root_path();
+
+    let mut mappings = Map::new();
+    for (name, service) in compose.get("services")?.as_object()? {
+        if !is_php_service(name, service, worktree) {
+            continue;
+        }
+        let volumes = service.get("volumes").and_then(Value::as_array);
+        for volume in volumes.into_iter().flatten() {
+            if let Some((source, target)) = bind_mount(volume)
+                && target.starts_with('/')
+                && let Some(local_path) = local_path(&root_path, source)
+            {
+                let target = match target.trim_end_matches('/') {
+                    "" => "/",
+                    target => target,
+                };
+                mappings.insert(target.to_string(), local_path.into());
+            }
+        }
+    }
+    (!mappings.is_empty()).then_some(mappings)
+}
+
+/// Services count as PHP services if their name or image mentions PHP, or if
+/// they build from a Dockerfile based on a PHP image.
+fn is_php_service(name: &str, service: &Value, worktree: &zed::Worktree) -> bool {
+    let mentions_php = |text: &str| text.to_lowercase().contains("php");
+    if mentions_php(name) {
+        return true;
+    }
+    if let Some(image) = service.get("image").and_then(Value::as_str) {
+        let image = image.to_lowercase();
+        return mentions_php(&image)
+            || image.starts_with("wordpress")
+            || image.starts_with("drupal");
+    }
+
+    let (context, dockerfile) = match service.get("build") {
+        Some(Value::String(context)) => (context.as_str(), "Dockerfile"),
+        Some(Value::Object(build)) => (
+            build.get("context").and_then(Value::as_str).unwrap_or("."),
+            build
+                .get("dockerfile")
+                .and_then(Value::as_str)
+                .unwrap_or("Dockerfile"),
+        ),
+        _ => return false,
+    };
+    let context = context.trim_start_matches("./").trim_end_matches('/');
+    let path = match context {
+        "" | "." => dockerfile.to_string(),
+        context => format!("{context}/{dockerfile}"),
+    };
+    worktree.read_text_file(&path).is_ok_and(|contents| {
+        contents
+            .lines()
+            .map(str::trim_start)
+            .filter(|line| line.len() > 5 && line[..5].eq_ignore_ascii_case("from "))
+            .any(mentions_php)
+    })
+}
+
+/// Returns the source and target of a bind mount in either the short
+/// (`./src:/var/www/html:cached`) or the long volume syntax.
+fn bind_mount(volume: &Value) -> Option<(&str, &str)> {
+    match volume {
+        Value::String(volume) => {
+            let mut parts = volume.splitn(3, ':');
+            Some((parts.next()?, parts.next()?))
+        }
+        Value::Object(volume) => {
+            if volume.get("type")?.as_str()? != "bind" {
+                return None;
+            }
+            Some((
+                volume.get("source")?.as_str()?,
+                volume.get("target")?.as_str()?,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Resolves a bind mount source against the worktree root. Named volumes and
+/// paths outside of the worktree have no local counterpart.
+fn local_path(root_path: &str, source: &str) -> Option<String> {
+    let source = source
+        .strip_prefix("${PWD}")
+        .or_else(|| source.strip_prefix("$PWD"))
+        .map(|rest| format!(".{rest}"))
+        .unwrap_or_else(|| source.to_string());
+    let source = source.trim_end_matches('/');
+
+    let relative = if let Some(relative) = source.strip_prefix(root_path)
+        && (relative.is_empty() || relative.starts_with('/'))
+    {
+        relative.trim_start_matches('/')
+    } else if source == "." {
+        ""
+    } else {
+        source.strip_prefix("./")?
+    };
+    if relative.split('/').any(|part| part == "..") {
+        return None;
+    }
+
+    Some(match relative {
+        "" => root_path.to_string(),
+        relative => format!("{root_path}/{relative}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use zed_extension_api::serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn finds_bind_mounts() {
+        for (volume, expected) in [
+            (
+                json!("./src:/var/www/html"),
+                Some(("./src", "/var/www/html")),
+            ),
+            (json!(".:/app:cached"), Some((".", "/app"))),
+            (
+                json!({ "type": "bind", "source": "./src", "target": "/app" }),
+                Some(("./src", "/app")),
+            ),
+            (
+                json!({ "type": "volume", "source": "data", "target": "/data" }),
+                None,
+            ),
+            (json!("/var/lib/mysql"), None),
+            (json!(3306), None),
+        ] {
+            assert_eq!(bind_mount(&volume), expected, "{volume}");
+        }
+    }
+
+    #[test]
+    fn resolves_local_paths_in_the_worktree() {
+        let root_path = "/home/me/project";
+        for (source, expected) in [
+            (".", Some("/home/me/project")),
+            ("./", Some("/home/me/project")),
+            ("./src", Some("/home/me/project/src")),
+            ("${PWD}/src", Some("/home/me/project/src")),
+            ("$PWD", Some("/home/me/project")),
+            ("/home/me/project/public/", Some("/home/me/project/public")),
+            ("/home/me/project-other", None),
+            ("./src/../../elsewhere", None),
+            ("/var/data", None),
+            ("data", None),
+        ] {
+            assert_eq!(
+                local_path(root_path, source).as_deref(),
+                expected,
+                "{source}"
+            );
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Support for `envFile`: dotenv files whose variables are added to the
+//! environment of a launched script, under the ones set explicitly in `env`.
+
+use std::collections::HashMap;
+
+use zed_extension_api::{
+    self as zed,
+    serde_json::{Map, Value},
+};
+
+use crate::paths;
+
+/// Moves the variables of the configuration's `envFile`s into its `env`.
+/// Variables set in `env` win over the files, and later files win over
+/// earlier ones.
+pub(super) fn apply(
+    configuration: &mut Map<String, Value>,
+    worktree: &zed::Worktree,
+) -> Result<(), String> {
+    let paths = match configuration.remove("envFile") {
+        None | Some(Value::Null) => return Ok(()),
+        Some(Value::String(path)) => vec![path],
+        Some(Value::Array(paths)) => paths
+            .into_iter()
+            .map(|path| match path {
+                Value::String(path) => Ok(path),
+                _ => Err("`envFile` must be a path or a list of paths".to_string()),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("`envFile` must be a path or a list of paths".into()),
+    };
+
+    let shell_env = worktree.shell_env().into_iter().collect::<HashMap<_, _>>();
+    let mut variables = Vec::<(String, String)>::new();
+    for path in paths {
+        let contents = read(worktree, &path)?;
+        for (key, value) in parse(&contents, &variables, &shell_env) {
+            variables.retain(|(existing, _)| *existing != key);
+            variables.push((key, value));
+        }
+    }
+
+    let env = configuration
+        .entry("env")
+        .or_insert_with(|| Value::Object(Default::default()));
+    let env = env
+        .as_object_mut()
+        .ok_or("`env` must be an object of environment variables")?;
+    for (key, value) in variables {
+        env.entry(key).or_insert_with(|| value.into());
+    }
+    Ok(())
+}
+
+/// Reads a file inside the worktree, given relative to its root or as an
+/// absolute path under it. Extensions can only read files outside of their
+/// own directory through the worktree, so other files are rejected.
+fn read(worktree: &zed::Worktree, path: &str) -> Result<String, String> {
+    let root_path = worktree.root_path();
+    let relative = match path.strip_prefix(&root_path) {
+        Some(relative) if relative.starts_with(['/', '\\']) => &relative[1..],
+        _ if !paths::is_absolute(path) => path.trim_start_matches("./"),
+        _ => {
+            return Err(format!(
+                "The `envFile` {path:?} is outside of the worktree, which extensions can't read"
+            ));
+        }
+    };
+    worktree
+        .read_text_file(relative)
+        .map_err(|error| format!("Could not read the `envFile` {path:?}: {error}"))
+}
+
+/// Parses a dotenv file: `KEY=value` lines with optional `export` prefixes,
+/// `#` comments, single-quoted literals and double-quoted or unquoted values
+/// that expand `$VAR`, `${VAR}` and `${VAR:-default}`.
+///
+/// Variables expand to those defined earlier in the file, then to
+/// `defined` (from earlier files), then to the shell environment.
+fn parse(
+    contents: &str,
+    defined: &[(String, String)],
+    shell_env: &HashMap<String, String>,
+) -> Vec<(String, String)> {
+    let mut variables = Vec::<(String, String)>::new();
+    let mut lines = contents.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim_start();
+
+        let lookup = |name: &str| {
+            variables
+                .iter()
+                .rev()
+                .chain(defined.iter().rev())
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| shell_env.get(name).cloned())
+        };
+
+        let value = if let Some(quoted) = value.strip_prefix('\'') {
+            read_quoted(quoted, '\'', &mut lines)
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            let quoted = read_quoted(quoted, '"', &mut lines);
+            expand(&unescape(&quoted), lookup)
+        } else {
+            let value = match value.find(" #") {
+                Some(comment) => &value[..comment],
+                None => value,
+            };
+            expand(value.trim_end(), lookup)
+        };
+
+        variables.retain(|(existing, _)| existing != key);
+        variables.push((key.to_string(), value));
+    }
+    variables
+}
+
+/// Returns the text up to the closing quote, which may be on a later line.
+fn read_quoted<'a>(start: &str, quote: char, lines: &mut impl Iterator<Item = &'a str>) -> String {
+    let mut text = String::new();
+    let mut line = start.to_string();
+    loop {
+        let mut escaped = false;
+        for (position, c) in line.char_indices() {
+            match c {
+                '\\' if quote == '"' && !escaped => escaped = true,
+                c if c == quote && !escaped => {
+                    text.push_str(&line[..position]);
+                    return text;
+                }
+                _ => escaped = false,
+            }
+        }
+        text.push_str(&line);
+        match lines.next() {
+            Some(next) => {
+                text.push('\n');
+                line = next.to_string();
+            }
+            None => return text,
+        }
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn expand(text: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        if let Some(braced) = after.strip_prefix('{')
+            && let Some(end) = braced.find('}')
+        {
+            let (name, default) = match braced[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&braced[..end], None),
+            };
+            let value = lookup(name).filter(|value| !value.is_empty() || default.is_none());
+            result.push_str(&value.unwrap_or_else(|| default.unwrap_or_default().to_string()));
+            rest = &braced[end + 1..];
+            continue;
+        }
+
+        let length = after
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(after.len());
+        if length == 0 {
+            result.push('$');
+        } else {
+            result.push_str(&lookup(&after[..length]).unwrap_or_default());
+        }
+        rest = &after[length..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dotenv_values() {
+        let contents = r#"
+# A comment
+export APP_ENV=local
+PLAIN = value # trailing comment
+EMPTY=
+SINGLE='literal $APP_ENV\n'
+DOUBLE="expanded $APP_ENV\tand ${MISSING:-default}"
+ESCAPED="a \"quote\""
+MULTILINE="first
+second"
+SINGLE_MULTILINE='one
+two'
+FROM_SHELL=$HOME/app
+FROM_EARLIER_FILE=${PREVIOUS}
+PLAIN=overridden
+"#;
+        let defined = [("PREVIOUS".to_string(), "earlier".to_string())];
+        let shell_env = HashMap::from([("HOME".to_string(), "/home/me".to_string())]);
+
+        assert_eq!(
+            parse(contents, &defined, &shell_env),
+            [
+                ("APP_ENV", "local"),
+                ("EMPTY", ""),
+                ("SINGLE", "literal $APP_ENV\\n"),
+                ("DOUBLE", "expanded local\tand default"),
+                ("ESCAPED", "a \"quote\""),
+                ("MULTILINE", "first\nsecond"),
+                ("SINGLE_MULTILINE", "one\ntwo"),
+                ("FROM_SHELL", "/home/me/app"),
+                ("FROM_EARLIER_FILE", "earlier"),
+                ("PLAIN", "overridden"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! Checks that the PHP binary a launch configuration runs can actually
+//! connect to the debugger, so misconfigured setups fail with an actionable
+//! error instead of a session that never stops.
+
+use zed_extension_api::{
+    self as zed,
+    process::Command,
+    serde_json::{Map, Value},
+};
+
+use crate::launch_command::runtime_setting;
+
+/// Prints how Xdebug is set up (`php --ri xdebug`) without running any code.
+/// It must not start a debug session either, so it never starts one with the
+/// request. `extension.toml` grants exactly these arguments.
+const PROBE_ARGS: [&str; 3] = ["-dxdebug.start_with_request=no", "--ri", "xdebug"];
+
+/// What `php --ri xdebug` reports.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct XdebugInfo {
+    version: String,
+    /// `xdebug.mode`, after `XDEBUG_MODE` in the environment PHP ran in.
+    mode: Option<String>,
+    client_port: Option<String>,
+}
+
+/// Runs the probe with the worktree's environment, then applies the launch's
+/// `-d` options and environment to what it reports, the way PHP would.
+pub(super) fn check(
+    configuration: &Map<String, Value>,
+    expected_port: Option<u64>,
+    worktree: &zed::Worktree,
+) -> Result<(), String> {
+    let php = configuration
+        .get("runtimeExecutable")
+        .and_then(Value::as_str)
+        .ok_or(
+            "Could not find PHP on the worktree's PATH. Install PHP or set `runtimeExecutable` \
+             in the debug configuration.",
+        )?;
+    let runtime_args = configuration
+        .get("runtimeArgs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|arg| arg.as_str().map(ToOwned::to_owned))
+        .collect::<Vec<_>>();
+    // The extension may only run the worktree's `php` by name (see
+    // `extension.toml`), and only without the launch's options, so binaries
+    // and options that change which extensions load aren't checked.
+    if php != "php" && worktree.which("php").as_deref() != Some(php) {
+        eprintln!(
+            "{}: skipping the Xdebug pre-flight check of {php}",
+            super::XDebug::NAME
+        );
+        return Ok(());
+    }
+    if changes_loaded_extensions(&runtime_args) {
+        eprintln!(
+            "{}: skipping the Xdebug pre-flight check, as `runtimeArgs` change which \
+             extensions PHP loads",
+            super::XDebug::NAME
+        );
+        return Ok(());
+    }
+
+    let shell_env = worktree.shell_env();
+    let output = match Command::new("php")
+        .args(PROBE_ARGS)
+        .envs(shell_env.clone())
+        .output()
+    {
+        Ok(output) => output,
+        Err(error) => {
+            // Not being allowed to run PHP doesn't mean the session won't work.
+            eprintln!(
+                "{}: skipping the Xdebug pre-flight check: {error}",
+                super::XDebug::NAME
+            );
+            return Ok(());
+        }
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let info = match parse_info(&stdout) {
+        Some(info) if output.status == Some(0) => info,
+        // `php --ri` fails with "Extension 'xdebug' not present." on stdout.
+        _ if stdout.contains("not present") => {
+            return Err(format!(
+                "Xdebug is not loaded by {php}. Install it (for example with `pecl install \
+                 xdebug`) and enable it with `zend_extension=xdebug` in php.ini; `{php} --ini` \
+                 lists the loaded ini files."
+            ));
+        }
+        _ => {
+            return Err(format!(
+                "Could not run {php}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    };
+
+    let env = shell_env
+        .into_iter()
+        .chain(
+            configuration
+                .get("env")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string()))),
+        )
+        .collect::<Vec<_>>();
+    validate(&info, &runtime_args, &env, php, expected_port)
+}
+
+/// Returns whether the PHP options skip php.ini (`-n`), use another one
+/// (`-c`) or load extensions themselves, so the probe, which runs without
+/// them, may see a different set of extensions than the launch.
+fn changes_loaded_extensions(runtime_args: &[String]) -> bool {
+    runtime_args
+        .iter()
+        .any(|arg| arg == "-n" || arg.starts_with("-c"))
+        || runtime_setting(runtime_args, "extension").is_some()
+        || runtime_setting(runtime_args, "zend_extension").is_some()
+}
+
+/// Parses the output of `php --ri xdebug`, or returns `None` if it doesn't
+/// report a version.
+fn parse_info(output: &str) -> Option<XdebugInfo> {
+    let mut info = XdebugInfo::default();
+    for line in output.lines() {
+        let mut columns = line.split("=>").map(str::trim);
+        let (Some(name), Some(value)) = (columns.next(), columns.next()) else {
+            continue;
+        };
+        // Settings list their local value first, then their php.ini one. The
+        // mode is listed as `xdebug.mode (through XDEBUG_MODE)` if the
+        // environment overrides it.
+        match name.split_whitespace().next().unwrap_or_default() {
+            "Version" => info.version = value.to_string(),
+            "xdebug.mode" => info.mode = Some(value.to_string()),
+            "xdebug.client_port" => info.client_port = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    (!info.version.is_empty()).then_some(info)
+}
+
+/// Checks the Xdebug that `php --ri xdebug` reported against the launch. `env`
+/// is the environment the script runs in, where later entries win.
+fn validate(
+    info: &XdebugInfo,
+    runtime_args: &[String],
+    env: &[(String, String)],
+    php: &str,
+    expected_port: Option<u64>,
+) -> Result<(), String> {
+    let version = &info.version;
+    let major = version
+        .split('.')
+        .next()
+        .and_then(|major| major.parse::<u64>().ok());
+    if major.is_none_or(|major| major < 3) {
+        return Err(format!(
+            "{php} loads Xdebug {version}, but debugging requires Xdebug 3 or later. Upgrade \
+             Xdebug (for example with `pecl install xdebug`)."
+        ));
+    }
+
+    // `XDEBUG_MODE` and `XDEBUG_CONFIG` override the ini settings, including
+    // the ones from `-d` options.
+    let env_var = |name: &str| {
+        env.iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let mode = env_var("XDEBUG_MODE")
+        .or_else(|| runtime_setting(runtime_args, "xdebug.mode"))
+        .or(info.mode.as_deref())
+        .unwrap_or_default();
+    if !mode.split(',').any(|mode| mode.trim() == "debug") {
+        return Err(format!(
+            "Xdebug's mode is {mode:?}, so it will never connect to the debugger. Add `debug` \
+             to `xdebug.mode` in php.ini or to `XDEBUG_MODE` in the configuration's `env`."
+        ));
+    }
+
+    let client_port = env_var("XDEBUG_CONFIG")
+        .and_then(|config| {
+            config
+                .split_whitespace()
+                .find_map(|setting| setting.strip_prefix("client_port="))
+        })
+        .or_else(|| runtime_setting(runtime_args, "xdebug.client_port"))
+        .or(info.client_port.as_deref())
+        .unwrap_or_default();
+    if let Some(expected_port) = expected_port
+        && client_port
+            .parse::<u64>()
+            .is_ok_and(|port| port != expected_port)
+    {
+        return Err(format!(
+            "Xdebug connects to port {client_port}, but the debugger listens on port \
+             {expected_port}. Change `xdebug.client_port` or the configuration's `port`."
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XDEBUG_INFO: &str = "xdebug
+
+Version => 3.3.2
+Support Xdebug on Patreon, GitHub, or as a business: https://xdebug.org/support
+
+             Enabled Features (through 'xdebug.mode' setting)
+Feature => Enabled/Disabled
+Development Helpers => ✘ disabled
+Step Debugger => ✘ disabled
+
+Directive => Local Value => Master Value
+xdebug.client_host => localhost => localhost
+xdebug.client_port => 9003 => 9003
+xdebug.mode => develop => develop
+xdebug.start_with_request => no => default
+";
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_xdebug_info() {
+        assert_eq!(
+            parse_info(XDEBUG_INFO),
+            Some(XdebugInfo {
+                version: "3.3.2".to_string(),
+                mode: Some("develop".to_string()),
+                client_port: Some("9003".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_info("xdebug.mode (through XDEBUG_MODE) => debug => develop\nVersion => 3.4.0")
+                .and_then(|info| info.mode),
+            Some("debug".to_string())
+        );
+        assert_eq!(parse_info("Extension 'xdebug' not present."), None);
+    }
+
+    #[test]
+    fn validates_the_effective_settings() {
+        let info = parse_info(XDEBUG_INFO).unwrap();
+        let env = |entries: &[(&str, &str)]| {
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        for (version, runtime_args, env, expected_port, is_valid) in [
+            (
+                "3.3.2",
+                &["-dxdebug.mode=debug"][..],
+                env(&[]),
+                Some(9003),
+                true,
+            ),
+            ("3.3.2", &[], env(&[]), Some(9003), false),
+            (
+                "3.3.2",
+                &[],
+                env(&[("XDEBUG_MODE", "debug,develop")]),
+                None,
+                true,
+            ),
+            (
+                "3.3.2",
+                &["-dxdebug.mode=debug"],
+                env(&[("XDEBUG_MODE", "debug"), ("XDEBUG_MODE", "off")]),
+                None,
+                false,
+            ),
+            (
+                "3.3.2",
+                &["-dxdebug.mode=debug"],
+                env(&[]),
+                Some(9010),
+                false,
+            ),
+            (
+                "3.3.2",
+                &["-dxdebug.mode=debug", "-dxdebug.client_port=9010"],
+                env(&[]),
+                Some(9010),
+                true,
+            ),
+            (
+                "3.3.2",
+                &["-dxdebug.mode=debug", "-dxdebug.client_port=9010"],
+                env(&[("XDEBUG_CONFIG", "client_port=9003")]),
+                Some(9010),
+                false,
+            ),
+            ("4.0.0", &["-dxdebug.mode=debug"], env(&[]), None, true),
+            ("2.9.8", &["-dxdebug.mode=debug"], env(&[]), None, false),
+        ] {
+            let info = XdebugInfo {
+                version: version.to_string(),
+                ..info.clone()
+            };
+            assert_eq!(
+                validate(&info, &strings(runtime_args), &env, "php", expected_port).is_ok(),
+                is_valid,
+                "{version} {runtime_args:?} {env:?} {expected_port:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn skips_options_that_load_extensions() {
+        for (runtime_args, expected) in [
+            (&["-dxdebug.mode=debug"][..], false),
+            (&["-n"], true),
+            (&["-c", "php.ini"], true),
+            (&["-cphp.ini"], true),
+            (&["-dzend_extension=xdebug"], true),
+            (&["-d", "extension=pdo_sqlite"], true),
+        ] {
+            assert_eq!(
+                changes_loaded_extensions(&strings(runtime_args)),
+                expected,
+                "{runtime_args:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Local development environments with fixed container layouts.
+//!
+//! DDEV, Lando and Laravel Sail all mount the project at a known path inside
+//! the PHP container and have Xdebug connect back to the host on port 9003.
+
+use zed_extension_api::{
+    self as zed,
+    serde_json::{Map, Value},
+};
+
+use super::XDebug;
+use crate::composer::ComposerJson;
+
+#[derive(Clone, Copy)]
+pub(super) enum Preset {
+    Ddev,
+    Lando,
+    Sail,
+}
+
+impl Preset {
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ddev" => Some(Self::Ddev),
+            "lando" => Some(Self::Lando),
+            "sail" => Some(Self::Sail),
+            _ => None,
+        }
+    }
+
+    pub(super) fn detect(worktree: &zed::Worktree) -> Option<Self> {
+        let has_file = |path: &str| worktree.read_text_file(path).is_ok();
+
+        if has_file(".ddev/config.yaml") {
+            Some(Self::Ddev)
+        } else if has_file(".lando.yml") {
+            Some(Self::Lando)
+        } else if ComposerJson::read(worktree)
+            .is_some_and(|composer| composer.requires("laravel/sail"))
+            && [
+                "compose.yaml",
+                "compose.yml",
+                "docker-compose.yml",
+                "docker-compose.yaml",
+            ]
+            .iter()
+            .any(|file| has_file(file))
+        {
+            Some(Self::Sail)
+        } else {
+            None
+        }
+    }
+
+    /// The path the project is mounted at inside the PHP container.
+    fn project_path(self) -> &'static str {
+        match self {
+            Self::Ddev | Self::Sail => "/var/www/html",
+            Self::Lando => "/app",
+        }
+    }
+
+    /// Fills in the path mappings and port of the environment, unless the
+    /// configuration already sets them.
+    pub(super) fn apply(self, configuration: &mut Map<String, Value>, root_path: &str) {
+        let has_path_mappings = configuration
+            .get("pathMappings")
+            .and_then(Value::as_object)
+            .is_some_and(|mappings| !mappings.is_empty());
+        if !has_path_mappings {
+            let mut mappings = Map::new();
+            mappings.insert(self.project_path().to_string(), root_path.into());
+            configuration.insert("pathMappings".into(), mappings.into());
+        }
+        configuration
+            .entry("port")
+            .or_insert_with(|| XDebug::DEFAULT_PORT.into());
+    }
+}
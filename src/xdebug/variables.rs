@@ -0,0 +1,189 @@
+//! Substitution of the VS Code variables that launch configurations copied
+//! from `launch.json` use, such as `${workspaceFolder}` or `${env:HOME}`.
+
+use std::collections::HashMap;
+
+use zed_extension_api::{
+    self as zed,
+    serde_json::{Map, Value},
+};
+
+use crate::paths;
+
+/// VS Code variables for the active file and the Zed task variables that
+/// replace them in `debug.json`.
+const FILE_VARIABLES: &[(&str, &str)] = &[
+    ("file", "$ZED_FILE"),
+    ("relativeFile", "$ZED_RELATIVE_FILE"),
+    ("relativeFileDirname", "$ZED_RELATIVE_DIR"),
+    ("fileBasename", "$ZED_FILENAME"),
+    ("fileBasenameNoExtension", "$ZED_STEM"),
+    ("fileDirname", "$ZED_DIRNAME"),
+    ("selectedText", "$ZED_SELECTED_TEXT"),
+    ("lineNumber", "$ZED_ROW"),
+];
+
+pub(super) struct Variables {
+    root_path: String,
+    separator: char,
+    port: Option<u64>,
+    env: HashMap<String, String>,
+}
+
+impl Variables {
+    /// `port` is the port the adapter listens on for Xdebug, or `None` if the
+    /// adapter picks one itself and substitutes `${port}` on its own.
+    pub(super) fn new(worktree: &zed::Worktree, port: Option<u64>) -> Self {
+        Self {
+            root_path: worktree.root_path(),
+            separator: paths::separator(),
+            port,
+            env: worktree.shell_env().into_iter().collect(),
+        }
+    }
+
+    /// Substitutes variables in every string of the configuration and resolves
+    /// relative local paths in `pathMappings` against the worktree root.
+    pub(super) fn substitute(&self, configuration: &mut Map<String, Value>) -> Result<(), String> {
+        for value in configuration.values_mut() {
+            self.substitute_value(value)?;
+        }
+
+        if let Some(mappings) = configuration
+            .get_mut("pathMappings")
+            .and_then(Value::as_object_mut)
+        {
+            for local_path in mappings.values_mut() {
+                if let Some(path) = local_path.as_str()
+                    && !paths::is_absolute(path)
+                {
+                    *local_path = self.resolve_relative(path).into();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn substitute_value(&self, value: &mut Value) -> Result<(), String> {
+        match value {
+            Value::String(text) => *text = self.substitute_str(text)?,
+            Value::Array(items) => {
+                for item in items {
+                    self.substitute_value(item)?;
+                }
+            }
+            Value::Object(entries) => {
+                for item in entries.values_mut() {
+                    self.substitute_value(item)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn substitute_str(&self, text: &str) -> Result<String, String> {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            let Some(length) = rest[start..].find('}') else {
+                break;
+            };
+            let name = &rest[start + 2..start + length];
+            result.push_str(&rest[..start]);
+            match self.resolve(name)? {
+                Some(value) => result.push_str(&value),
+                None => result.push_str(&rest[start..=start + length]),
+            }
+            rest = &rest[start + length + 1..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Returns the value of a variable, or `None` to leave it as it is.
+    fn resolve(&self, name: &str) -> Result<Option<String>, String> {
+        if let Some(variable) = name.strip_prefix("env:") {
+            // Like VS Code, unset environment variables are empty.
+            return Ok(Some(self.env.get(variable).cloned().unwrap_or_default()));
+        }
+        if let Some((_, zed_variable)) = FILE_VARIABLES.iter().find(|(vscode, _)| *vscode == name) {
+            return Err(format!(
+                "`${{{name}}}` depends on the active editor, which debug adapters can't see. \
+                 Use `{zed_variable}` instead."
+            ));
+        }
+
+        Ok(match name {
+            "workspaceFolder" | "workspaceRoot" | "cwd" => Some(self.root_path.clone()),
+            "workspaceFolderBasename" => self
+                .root_path
+                .rsplit(['/', '\\'])
+                .next()
+                .map(ToOwned::to_owned),
+            "userHome" => self
+                .env
+                .get("HOME")
+                .or_else(|| self.env.get("USERPROFILE"))
+                .cloned(),
+            "pathSeparator" | "/" => Some(self.separator.to_string()),
+            "port" => self.port.map(|port| port.to_string()),
+            _ => None,
+        })
+    }
+
+    fn resolve_relative(&self, path: &str) -> String {
+        match path.trim_start_matches("./").trim_end_matches('/') {
+            "" | "." => self.root_path.clone(),
+            relative => format!("{}/{relative}", self.root_path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(separator: char, port: Option<u64>) -> Variables {
+        Variables {
+            root_path: "/home/me/project".to_string(),
+            separator,
+            port,
+            env: HashMap::from([("HOME".to_string(), "/home/me".to_string())]),
+        }
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        let variables = variables('/', Some(9003));
+        for (text, expected) in [
+            ("${workspaceFolder}/public", "/home/me/project/public"),
+            ("${workspaceFolderBasename}", "project"),
+            ("${userHome}/.composer", "/home/me/.composer"),
+            ("${env:HOME}:${env:UNSET}", "/home/me:"),
+            ("src${pathSeparator}index.php", "src/index.php"),
+            ("-dxdebug.client_port=${port}", "-dxdebug.client_port=9003"),
+            ("${unknown} stays", "${unknown} stays"),
+            ("${unterminated", "${unterminated"),
+        ] {
+            assert_eq!(
+                variables.substitute_str(text).as_deref(),
+                Ok(expected),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_the_port_to_the_adapter_and_rejects_editor_variables() {
+        assert_eq!(
+            variables('\\', None).substitute_str("${port}${/}"),
+            Ok("${port}\\".to_string())
+        );
+        assert!(
+            variables('/', None)
+                .substitute_str("${file}")
+                .is_err_and(|error| error.contains("$ZED_FILE"))
+        );
+    }
+}
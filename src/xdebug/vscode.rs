@@ -0,0 +1,156 @@
+//! Translation of the `php` configurations in `.vscode/launch.json` into the
+//! configuration format of this adapter.
+//!
+//! Finding them is up to Zed, which imports `.vscode/launch.json` itself and
+//! starts its `php` entries with the Xdebug adapter. The extension API has no
+//! way to contribute scenarios from a file, so configurations only get here
+//! through that importer (or from `.zed/debug.json`), never through
+//! `dap_config_to_scenario`.
+
+use zed_extension_api::{
+    self as zed,
+    serde_json::{Map, Value},
+};
+
+/// Keys VS Code itself interprets, which mean nothing to the adapter.
+const VSCODE_ONLY_KEYS: &[&str] = &[
+    "type",
+    "name",
+    "preLaunchTask",
+    "postDebugTask",
+    "presentation",
+    "internalConsoleOptions",
+    "console",
+    "serverReadyAction",
+    "debugServer",
+    "windows",
+    "osx",
+    "linux",
+];
+
+/// Rewrites a configuration written for VS Code so it validates and behaves
+/// the same way here. Nothing tells which editor a configuration was written
+/// for, so this applies to every configuration: in particular, any launch
+/// without a `program` becomes an attach without a preset. `platform` picks the
+/// `windows`, `osx` or `linux` overrides that apply.
+pub(super) fn translate(configuration: &mut Map<String, Value>, platform: zed::Os) {
+    let platform_key = match platform {
+        zed::Os::Mac => "osx",
+        zed::Os::Linux => "linux",
+        zed::Os::Windows => "windows",
+    };
+    if let Some(Value::Object(overrides)) = configuration.remove(platform_key) {
+        configuration.extend(overrides);
+    }
+    for key in VSCODE_ONLY_KEYS {
+        configuration.remove(*key);
+    }
+
+    // Before `pathMappings`, vscode-php-debug mapped a single directory.
+    let server_root = configuration.remove("serverSourceRoot");
+    let local_root = configuration.remove("localSourceRoot");
+    if let (Some(Value::String(server_root)), Some(local_root)) = (server_root, local_root) {
+        let mappings = configuration
+            .entry("pathMappings")
+            .or_insert_with(|| Value::Object(Default::default()));
+        if let Some(mappings) = mappings.as_object_mut() {
+            mappings.entry(server_root).or_insert(local_root);
+        }
+    }
+
+    // VS Code's "Listen for Xdebug" is a launch without a program, which is
+    // what this adapter calls attaching.
+    if configuration.get("request").and_then(Value::as_str) == Some("launch")
+        && !configuration.contains_key("program")
+    {
+        configuration.insert("request".into(), "attach".into());
+        // The environment detection of attach configurations would override
+        // the hand-tuned settings.
+        configuration
+            .entry("preset")
+            .or_insert_with(|| "none".into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zed_extension_api::serde_json::json;
+
+    use super::*;
+
+    fn translated(configuration: Value, platform: zed::Os) -> Value {
+        let Value::Object(mut configuration) = configuration else {
+            unreachable!();
+        };
+        translate(&mut configuration, platform);
+        Value::Object(configuration)
+    }
+
+    #[test]
+    fn translates_launch_json_configurations() {
+        for (configuration, platform, expected) in [
+            (
+                json!({
+                    "type": "php",
+                    "name": "Launch",
+                    "request": "launch",
+                    "program": "${file}",
+                    "console": "integratedTerminal",
+                    "windows": { "runtimeExecutable": "C:\\php\\php.exe" },
+                }),
+                zed::Os::Windows,
+                json!({
+                    "request": "launch",
+                    "program": "${file}",
+                    "runtimeExecutable": "C:\\php\\php.exe",
+                }),
+            ),
+            (
+                json!({
+                    "request": "launch",
+                    "program": "index.php",
+                    "windows": { "runtimeExecutable": "C:\\php\\php.exe" },
+                }),
+                zed::Os::Linux,
+                json!({ "request": "launch", "program": "index.php" }),
+            ),
+            (
+                json!({
+                    "name": "Listen for Xdebug",
+                    "request": "launch",
+                    "port": 9003,
+                    "serverSourceRoot": "/var/www/html",
+                    "localSourceRoot": "${workspaceFolder}",
+                }),
+                zed::Os::Mac,
+                json!({
+                    "request": "attach",
+                    "port": 9003,
+                    "preset": "none",
+                    "pathMappings": { "/var/www/html": "${workspaceFolder}" },
+                }),
+            ),
+            (
+                json!({
+                    "request": "launch",
+                    "preset": "ddev",
+                    "serverSourceRoot": "/app",
+                    "localSourceRoot": ".",
+                    "pathMappings": { "/app": "./src" },
+                }),
+                zed::Os::Linux,
+                json!({
+                    "request": "attach",
+                    "preset": "ddev",
+                    "pathMappings": { "/app": "./src" },
+                }),
+            ),
+        ] {
+            assert_eq!(
+                translated(configuration.clone(), platform),
+                expected,
+                "{configuration}"
+            );
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//! A reader for the subset of YAML that project configuration files such as
+//! `compose.yaml`, `.ddev/config.yaml` or `.lando.yml` use in practice: block
+//! mappings and sequences, plain and quoted scalars, single-line flow
+//! collections and literal/folded block scalars. Anchors, aliases, merge keys,
+//! tags and multi-line flow collections are not supported.
+
+use zed_extension_api::serde_json::{Map, Value};
+
+struct Line<'a> {
+    indent: usize,
+    text: &'a str,
+}
+
+/// Parses a YAML document into JSON, or returns `None` if it uses syntax this
+/// reader does not understand.
+pub(crate) fn parse(source: &str) -> Option<Value> {
+    let mut lines = source
+        .lines()
+        .filter_map(|line| {
+            let text = strip_comment(line).trim_end();
+            let trimmed = text.trim_start();
+            if trimmed.is_empty() || trimmed == "---" || trimmed == "..." {
+                return None;
+            }
+            Some(Line {
+                indent: text.len() - trimmed.len(),
+                text: trimmed,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if lines.iter().any(|line| uses_references(line.text)) {
+        return None;
+    }
+    if lines.is_empty() {
+        return Some(Value::Null);
+    }
+    let indent = lines[0].indent;
+    let mut index = 0;
+    let value = parse_block(&mut lines, &mut index, indent)?;
+    (index == lines.len()).then_some(value)
+}
+
+fn parse_block(lines: &mut [Line], index: &mut usize, indent: usize) -> Option<Value> {
+    if is_sequence_item(lines[*index].text) {
+        parse_sequence(lines, index, indent)
+    } else {
+        parse_mapping(lines, index, indent)
+    }
+}
+
+fn parse_sequence(lines: &mut [Line], index: &mut usize, indent: usize) -> Option<Value> {
+    let mut items = Vec::new();
+    while *index < lines.len()
+        && lines[*index].indent == indent
+        && is_sequence_item(lines[*index].text)
+    {
+        let item = lines[*index].text[1..].trim_start();
+        if item.is_empty() {
+            *index += 1;
+            items.push(parse_nested(lines, index, indent)?);
+        } else if split_key(item).is_some() {
+            // A mapping that starts on the same line as the dash: re-read the
+            // rest of the line as the first entry of a more indented mapping.
+            let offset = lines[*index].text.len() - item.len();
+            lines[*index].indent += offset;
+            lines[*index].text = item;
+            items.push(parse_mapping(lines, index, indent + offset)?);
+        } else {
+            *index += 1;
+            items.push(parse_scalar(item));
+        }
+    }
+    Some(Value::Array(items))
+}
+
+fn parse_mapping(lines: &mut [Line], index: &mut usize, indent: usize) -> Option<Value> {
+    let mut map = Map::new();
+    while *index < lines.len() && lines[*index].indent == indent {
+        let (key, value) = split_key(lines[*index].text)?;
+        *index += 1;
+        let value = match value {
+            "" => {
+                // Sequences may sit at the same indentation as their key.
+                if *index < lines.len()
+                    && lines[*index].indent == indent
+                    && is_sequence_item(lines[*index].text)
+                {
+                    parse_sequence(lines, index, indent)?
+                } else {
+                    parse_nested(lines, index, indent)?
+                }
+            }
+            "|" | "|-" | ">" | ">-" => {
+                let separator = if value.starts_with('|') { "\n" } else { " " };
+                let mut text = Vec::new();
+                while *index < lines.len() && lines[*index].indent > indent {
+                    text.push(lines[*index].text);
+                    *index += 1;
+                }
+                Value::String(text.join(separator))
+            }
+            value => parse_scalar(value),
+        };
+        map.insert(unquote(key).to_string(), value);
+    }
+    (*index == lines.len() || lines[*index].indent < indent).then_some(Value::Object(map))
+}
+
+/// Parses the block nested under the line before `index`, if there is one.
+fn parse_nested(lines: &mut [Line], index: &mut usize, indent: usize) -> Option<Value> {
+    match lines.get(*index) {
+        Some(line) if line.indent > indent => {
+            let indent = line.indent;
+            parse_block(lines, index, indent)
+        }
+        _ => Some(Value::Null),
+    }
+}
+
+fn parse_scalar(text: &str) -> Value {
+    let text = text.trim();
+    if let Some(items) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        return Value::Array(split_flow(items).into_iter().map(parse_scalar).collect());
+    }
+    if let Some(entries) = text.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+        return Value::Object(
+            split_flow(entries)
+                .into_iter()
+                .filter_map(|entry| {
+                    let (key, value) = split_key(entry)?;
+                    Some((unquote(key).to_string(), parse_scalar(value)))
+                })
+                .collect(),
+        );
+    }
+    if text.starts_with(['"', '\'']) {
+        return Value::String(unquote(text).to_string());
+    }
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => Value::Null,
+        "true" | "True" | "TRUE" => Value::Bool(true),
+        "false" | "False" | "FALSE" => Value::Bool(false),
+        _ => text
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(text.to_string())),
+    }
+}
+
+/// Returns whether a line defines an anchor (`&name`), refers to one
+/// (`*name`) or merges one (`<<: *name`), which this reader can't resolve.
+fn uses_references(text: &str) -> bool {
+    let mut value = text;
+    while is_sequence_item(value) {
+        value = value[1..].trim_start();
+    }
+    if let Some((key, rest)) = split_key(value) {
+        if key == "<<" || is_reference(key) {
+            return true;
+        }
+        value = rest;
+    }
+    if let Some(items) = value
+        .strip_prefix(['[', '{'])
+        .and_then(|items| items.strip_suffix([']', '}']))
+    {
+        return split_flow(items).into_iter().any(uses_references);
+    }
+    is_reference(value)
+}
+
+fn is_reference(token: &str) -> bool {
+    token
+        .strip_prefix(['&', '*'])
+        .and_then(|name| name.chars().next())
+        .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Splits `key: value` (or `key:`) at the first colon outside of quotes that
+/// is followed by whitespace or the end of the line.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (position, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if opens_quote(previous) => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, ':') => {
+                let rest = &text[position + 1..];
+                if rest.is_empty() || rest.starts_with([' ', '\t']) {
+                    return Some((text[..position].trim(), rest.trim()));
+                }
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    None
+}
+
+/// Splits the inside of a single-line flow collection at top-level commas.
+fn split_flow(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut depth = 0;
+    let mut start = 0;
+    let mut previous = ' ';
+    for (position, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if opens_quote(previous) => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '[' | '{') => depth += 1,
+            (None, ']' | '}') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(text[start..position].trim());
+                start = position + 1;
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    parts.push(text[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// Quotes only start a scalar at the beginning of a token, so apostrophes in
+/// plain words such as `don't` are not quotes.
+fn opens_quote(previous: char) -> bool {
+    previous.is_whitespace() || matches!(previous, ':' | '-' | '[' | '{' | ',')
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .or_else(|| text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')))
+        .unwrap_or(text)
+}
+
+/// Removes a trailing `# comment`, ignoring `#` inside quotes or words.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (position, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if opens_quote(previous) => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '#') if previous.is_whitespace() => return &line[..position],
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use zed_extension_api::serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parses_compose_volumes() {
+        let compose = r#"
+services:
+  app:
+    image: php:8.3-fpm  # the PHP service
+    volumes:
+      - ./src:/var/www/html:cached
+      - "./config/php.ini:/usr/local/etc/php/php.ini"
+      - type: bind
+        source: ./public
+        target: /var/www/public
+        read_only: true
+      - type: volume
+        source: data
+        target: /var/lib/data
+  db:
+    ports: ["3306:3306", '33060:33060']
+"#;
+        assert_eq!(
+            parse(compose),
+            Some(json!({
+                "services": {
+                    "app": {
+                        "image": "php:8.3-fpm",
+                        "volumes": [
+                            "./src:/var/www/html:cached",
+                            "./config/php.ini:/usr/local/etc/php/php.ini",
+                            {
+                                "type": "bind",
+                                "source": "./public",
+                                "target": "/var/www/public",
+                                "read_only": true,
+                            },
+                            {
+                                "type": "volume",
+                                "source": "data",
+                                "target": "/var/lib/data",
+                            },
+                        ],
+                    },
+                    "db": { "ports": ["3306:3306", "33060:33060"] },
+                },
+            }))
+        );
+    }
+
+    #[test]
+    fn parses_scalars_and_blocks() {
+        for (source, expected) in [
+            ("", Some(Value::Null)),
+            ("key: value", Some(json!({ "key": "value" }))),
+            ("port: 9003", Some(json!({ "port": 9003 }))),
+            ("enabled: ~", Some(json!({ "enabled": null }))),
+            ("name: don't", Some(json!({ "name": "don't" }))),
+            (
+                "url: http://localhost#top",
+                Some(json!({ "url": "http://localhost#top" })),
+            ),
+            (
+                "env: { A: 1, B: 'two' }",
+                Some(json!({ "env": { "A": 1, "B": "two" } })),
+            ),
+            ("list:\n- a\n- b", Some(json!({ "list": ["a", "b"] }))),
+            (
+                "script: |\n  one\n  two",
+                Some(json!({ "script": "one\ntwo" })),
+            ),
+            (
+                "script: >\n  one\n  two",
+                Some(json!({ "script": "one two" })),
+            ),
+            (
+                "command: make && make test",
+                Some(json!({ "command": "make && make test" })),
+            ),
+            ("pattern: '*.php'", Some(json!({ "pattern": "*.php" }))),
+            ("a: 1\n  b: 2", None),
+            ("not yaml", None),
+        ] {
+            assert_eq!(parse(source), expected, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_anchors_and_merge_keys() {
+        for source in [
+            "x-php: &php\n  image: php:8.3\nservices:\n  app: *php",
+            "services:\n  app:\n    <<: *php\n    ports: [80]",
+            "volumes: [*code, ./logs:/logs]",
+            "volumes:\n  - *code",
+            "env: { A: &a 1 }",
+        ] {
+            assert_eq!(parse(source), None, "{source:?}");
+        }
+    }
+}